@@ -1,17 +1,17 @@
 use axum::{
-    body::Bytes,
-    body::StreamBody,
     extract::{Path, Query},
+    response::IntoResponse,
     Extension, Json,
 };
 use bson::{doc, to_document};
-use futures::Stream;
 use serde_json::{json, Value};
 
+use crate::body::Payload;
 use crate::aggregate::structs::{Aggregate, AggregateRaw};
 use crate::error::Error as RestError;
 use crate::find::structs::Explain;
-use crate::queries::{ExplainFormat, QueriesFormat};
+use crate::framing::Framing;
+use crate::queries::{ExplainFormat, QueriesConcern, QueriesFormat, QueriesPage};
 use crate::scopes::AuthorizeScope;
 use crate::State;
 
@@ -20,13 +20,32 @@ pub async fn aggregate(
     Extension(scopes): Extension<AuthorizeScope>,
     Path((db, coll)): Path<(String, String)>,
     queries: Query<QueriesFormat>,
-    Json(payload): Json<Aggregate>,
-) -> Result<StreamBody<impl Stream<Item = Result<Bytes, RestError>>>, RestError> {
+    framing: Framing,
+    concern: Query<QueriesConcern>,
+    Payload(payload): Payload<Aggregate>,
+) -> Result<impl IntoResponse, RestError> {
     // Validate that the client has access to this database
-    scopes.write(&db)?;
+    scopes.aggregate(&db, &coll, &payload.pipeline)?;
 
     log::info!("{{\"fn\": \"aggregate\", \"db\": \"{}\", \"coll\": \"{}\"}}", &db, &coll);
-    state.db.aggregate(&db, &coll, payload, queries).await
+    state.db.aggregate(&db, &coll, payload, queries, framing, &concern, &scopes).await
+}
+
+pub async fn aggregate_page(
+    Extension(state): Extension<State>,
+    Extension(scopes): Extension<AuthorizeScope>,
+    Path((db, coll)): Path<(String, String)>,
+    queries: Query<QueriesPage>,
+    concern: Query<QueriesConcern>,
+    Payload(payload): Payload<Aggregate>,
+) -> Result<Json<Value>, RestError> {
+    // Validate that the client has access to this database
+    scopes.aggregate(&db, &coll, &payload.pipeline)?;
+
+    log::info!("{{\"fn\": \"aggregate_page\", \"db\": \"{}\", \"coll\": \"{}\"}}", &db, &coll);
+    Ok(Json(json!(
+        state.db.aggregate_page(&db, &coll, payload, &queries, &concern, &scopes).await?
+    )))
 }
 
 pub async fn aggregate_explain(
@@ -34,10 +53,10 @@ pub async fn aggregate_explain(
     Extension(scopes): Extension<AuthorizeScope>,
     Path((db, coll)): Path<(String, String)>,
     queries: Query<ExplainFormat>,
-    Json(payload): Json<Aggregate>,
+    Payload(payload): Payload<Aggregate>,
 ) -> Result<Json<Value>, RestError> {
     // Validate that the client has access to this database
-    scopes.write(&db)?;
+    scopes.aggregate(&db, &coll, &payload.pipeline)?;
 
     log::info!("{{\"fn\": \"aggregate_explain\", \"db\": \"{}\", \"coll\": \"{}\"}}", &db, &coll);
 
@@ -8,14 +8,16 @@ use axum::{
 };
 use chrono::Utc;
 use hyper::{Body, Uri};
-use jsonwebtoken::jwk::AlgorithmParameters;
-use jsonwebtoken::{decode, decode_header, jwk, DecodingKey, Validation};
+use jsonwebtoken::jwk::{AlgorithmParameters, EllipticCurve};
+use jsonwebtoken::{decode, decode_header, jwk, Algorithm, DecodingKey, Validation};
 use serde_json::{Value, json};
 use serde::{Deserialize, Serialize};
 use std::sync::{Arc, Mutex};
 
 use crate::error::Error as RestError;
 use crate::https::HttpsClient;
+use crate::issuers::{self, ClaimMapping, IssuerConfig};
+use crate::redaction::Policy;
 use crate::scopes::AuthorizeScope;
 
 type MyResult<T> = std::result::Result<T, RestError>;
@@ -24,13 +26,19 @@ type MyResult<T> = std::result::Result<T, RestError>;
 pub struct AuthJwks {
     replicaset: Option<Vec<String>>,
     noauth: bool,
-    keys: JwksKeys,
+    issuers: Vec<JwksKeys>,
+    policy: Arc<Policy>,
 }
 
+// Keys, and the rules for trusting tokens signed with them, for a single issuer
 #[derive(Clone)]
 pub struct JwksKeys {
-    uri: Option<String>,
-    audience: Option<String>,
+    name: String,
+    uri: Arc<Mutex<Option<String>>>,
+    issuer: Option<String>,
+    discovery: bool,
+    audiences: Vec<String>,
+    claims: ClaimMapping,
     jwks: Arc<Mutex<Value>>,
     last_read: Arc<Mutex<i64>>,
     client: HttpsClient,
@@ -39,16 +47,28 @@ pub struct JwksKeys {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,
-    pub exp: i64,
-    pub aud: String,
-    pub iss: String,
+    pub exp: Option<i64>,
+    pub iss: Option<String>,
     pub jti: String,
     pub scp: Vec<String> 
 }
 
 impl AuthJwks {
     pub fn new(args: Args, set: Option<String>) -> MyResult<Self> {
-        let jwks_keys = JwksKeys::new(args.clone())?;
+        let policy = Arc::new(Policy::load(args.redaction.as_deref())?);
+
+        let issuers = match args.noauth {
+            true => Vec::new(),
+            false => issuers::load(&args)?
+                .into_iter()
+                .map(JwksKeys::new)
+                .collect(),
+        };
+
+        // Get every issuer's keys in the background, so tokens can be matched to them by kid
+        for issuer in &issuers {
+            issuer.renew();
+        }
 
         // Create array of replicasets. One for override, the other for the native replicaset name.
         let mut replicaset = Vec::new();
@@ -70,31 +90,104 @@ impl AuthJwks {
 
         Ok(AuthJwks {
             noauth: args.noauth,
-            keys: jwks_keys,
+            issuers,
+            policy,
             replicaset: replicaset_switch,
         })
     }
 
-    #[allow(dead_code)]
-    pub async fn keys(&mut self) -> Result<jwk::JwkSet, RestError> {
-        self.keys.keys().await
+    pub async fn scopes(&mut self, header: &str) -> Result<AuthorizeScope, RestError> {
+        let token = match header.split(' ').nth(1) {
+            Some(token) => token,
+            None => {
+                log::trace!("\"Authorization header is not a bearer token\"");
+                return Err(RestError::JwtDecode);
+            }
+        };
+
+        let claims = self.issuer(token).await?.scopes(token).await?;
+        let redactions = self.policy.redactions(&claims.scp);
+        Ok(AuthorizeScope::new(self.replicaset.clone(), claims)?.with_redactions(redactions))
     }
 
-    pub async fn scopes(&mut self, token: &str) -> Result<AuthorizeScope, RestError> {
-        let claims = self.keys.scopes(token).await?;
-        AuthorizeScope::new(self.replicaset.clone(), claims)
+    // Scopes for clients when auth is off, which are still subject to redaction rules that apply to everyone
+    pub fn anonymous(&self) -> AuthorizeScope {
+        AuthorizeScope::default().with_redactions(self.policy.redactions(&[]))
+    }
+
+    // Pick the issuer to verify a token against, by its unverified iss, then by its kid among
+    // the keys already fetched, so a request never waits on every issuer's jwks
+    async fn issuer(&self, token: &str) -> Result<&JwksKeys, RestError> {
+        // A lone issuer gets its keys fetched while verifying, even if the startup fetch failed
+        if let [issuer] = self.issuers.as_slice() {
+            return Ok(issuer);
+        };
+
+        if let Some(iss) = unverified_issuer(token) {
+            if let Some(issuer) = self
+                .issuers
+                .iter()
+                .find(|issuer| issuer.issuer.as_deref() == Some(iss.as_str()))
+            {
+                return Ok(issuer);
+            }
+        };
+
+        let kid = match decode_header(token)?.kid {
+            Some(k) => k,
+            None => {
+                log::trace!("\"Token doesn't have a `kid` header field\"");
+                return Err(RestError::JwtDecode);
+            }
+        };
+
+        if let Some(issuer) = self
+            .issuers
+            .iter()
+            .find(|issuer| matches!(issuer.loaded(), Some(jwks) if jwks.find(&kid).is_some()))
+        {
+            return Ok(issuer);
+        };
+
+        // The kid may be in keys that failed to load, or are due a refresh, so try again for later tokens
+        for issuer in &self.issuers {
+            issuer.renew();
+        }
+
+        log::warn!("\"No trusted issuer has a JWK for the given kid\"");
+        Err(RestError::JwtDecode)
+    }
+
+    pub fn noauth(&self) -> bool {
+        self.noauth
+    }
+
+    // Authorize an Authorization header outside of the auth middleware
+    pub async fn authorize(&mut self, header: Option<&str>) -> Result<AuthorizeScope, RestError> {
+        if self.noauth {
+            return Ok(self.anonymous());
+        }
+
+        match header {
+            Some(header) => self.scopes(header).await,
+            None => Err(RestError::UnauthorizedClient),
+        }
     }
 }
 
 impl JwksKeys {
-    pub fn new(args: Args) -> MyResult<Self> {
-        Ok(JwksKeys {
-            uri: args.jwks,
-            audience: args.audience,
+    pub fn new(config: IssuerConfig) -> Self {
+        JwksKeys {
+            name: config.name(),
+            discovery: config.jwks.is_none() && config.issuer.is_some(),
+            uri: Arc::new(Mutex::new(config.jwks)),
+            issuer: config.issuer,
+            audiences: config.audiences,
+            claims: config.claims,
             jwks: Arc::new(Mutex::new(json!(null))),
             last_read: Arc::new(Mutex::new(0i64)),
             client: HttpsClient::default(),
-        })
+        }
     }
 
     #[async_recursion]
@@ -102,24 +195,84 @@ impl JwksKeys {
         let jwks = self.jwks.lock().unwrap().clone();
         match jwks {
             Value::Null => {
-                log::debug!("Getting keys from {}", self.uri.as_ref().unwrap());
+                log::debug!("Getting keys");
                 self.get_keys().await?;
                 self.keys().await
             }
             _ => {
-                self.renew().await;
+                self.renew();
                 log::trace!("Returning known keys");
-                let j: jwk::JwkSet = serde_json::from_value(jwks)?;
-                log::trace!("keys: {:?}", j);
-                Ok(j)
+                key_set(&jwks)
             }
         }
     }
 
+    // The keys fetched so far, without going out to get them
+    fn loaded(&self) -> Option<jwk::JwkSet> {
+        let jwks = self.jwks.lock().unwrap().clone();
+        match jwks {
+            Value::Null => None,
+            _ => key_set(&jwks).ok(),
+        }
+    }
+
     pub async fn get_keys(&self) -> Result<(), RestError> {
-        let uri = Uri::try_from(self.uri.clone().unwrap().to_string())?;
+        // An explicit jwks url wins, otherwise it is rediscovered along with the keys
+        if self.discovery {
+            self.discover().await?;
+        }
 
+        let uri = match self.uri.lock().unwrap().clone() {
+            Some(uri) => uri,
+            None => return Err(RestError::Discovery("No jwks uri".to_string())),
+        };
         log::debug!("jwks uri: {}", uri);
+        let body = self.fetch(&uri).await?;
+
+        // Save jwks
+        let mut jwks = self.jwks.lock().unwrap();
+        *jwks = body;
+
+        // Set last_read field
+        let now = Utc::now().timestamp();
+        let mut last_read = self.last_read.lock().unwrap();
+        *last_read = now;
+
+        Ok(())
+    }
+
+    // Look up the jwks url from the issuer's OpenID Connect discovery document
+    async fn discover(&self) -> Result<(), RestError> {
+        let issuer = self.issuer.clone().unwrap_or_default();
+        let uri = format!(
+            "{}/.well-known/openid-configuration",
+            issuer.trim_end_matches('/')
+        );
+
+        log::debug!("discovery uri: {}", uri);
+        let config = self.fetch(&uri).await?;
+
+        // The document has to be for the issuer we were configured with
+        if config["issuer"].as_str() != Some(issuer.as_str()) {
+            return Err(RestError::Discovery(format!(
+                "Issuer mismatch, expected {} but got {}",
+                issuer, config["issuer"]
+            )));
+        }
+
+        let jwks_uri = match config["jwks_uri"].as_str() {
+            Some(jwks_uri) => jwks_uri.to_string(),
+            None => return Err(RestError::Discovery("Missing jwks_uri".to_string())),
+        };
+
+        let mut uri = self.uri.lock().unwrap();
+        *uri = Some(jwks_uri);
+
+        Ok(())
+    }
+
+    async fn fetch(&self, uri: &str) -> Result<Value, RestError> {
+        let uri = Uri::try_from(uri)?;
 
         let req = Request::builder()
             .method("GET")
@@ -129,39 +282,27 @@ impl JwksKeys {
 
         let response = self.client.request(req).await?;
 
-        let body = match response.status().as_u16() {
+        match response.status().as_u16() {
             200 => {
                 let contents = hyper::body::to_bytes(response.into_body()).await?;
-                let string: Value = serde_json::from_slice(&contents)?;
-                string
+                Ok(serde_json::from_slice(&contents)?)
             }
             _ => {
                 log::debug!(
                     "Got bad status code getting config: {}",
                     response.status().as_u16()
                 );
-                return Err(RestError::BadStatusCode);
+                Err(RestError::BadStatusCode)
             }
-        };
-
-        // Save jwks
-        let mut jwks = self.jwks.lock().unwrap();
-        *jwks = body;
-
-        // Set last_read field
-        let now = Utc::now().timestamp();
-        let mut last_read = self.last_read.lock().unwrap();
-        *last_read = now;
-
-        Ok(())
+        }
     }
 
-    pub async fn renew(&self) {
+    pub fn renew(&self) {
         let last_read = self.last_read.lock().expect("Error getting last_read");
         let diff = Utc::now().timestamp() - *last_read;
         if diff >= 360 {
-            log::debug!("jwks has expired, kicking off job to get keys");
-            metrics::increment_counter!("proxima_jwks_renew_attempts_total");
+            log::debug!("jwks for {} has expired, kicking off job to get keys", self.name);
+            metrics::increment_counter!("proxima_jwks_renew_attempts_total", "issuer" => self.name.clone());
             drop(last_read);
 
             // Kick off background thread to update config
@@ -169,8 +310,8 @@ impl JwksKeys {
             tokio::spawn(async move {
                 log::debug!("Kicking off background thread to renew jwks");
                 if let Err(e) = me.get_keys().await {
-                    log::error!("Error gettings updated jwks: {}", e);
-                    metrics::increment_counter!("proxima_jwks_renew_failures_total");
+                    log::error!("Error gettings updated jwks for {}: {}", me.name, e);
+                    metrics::increment_counter!("proxima_jwks_renew_failures_total", "issuer" => me.name.clone());
                 }
             });
         } else {
@@ -178,13 +319,11 @@ impl JwksKeys {
         }
     }
 
-    pub async fn scopes(&self, header: &str) -> Result<Claims, RestError> {
-        self.renew().await;
-
-        let token: Vec<&str> = header.split(' ').collect();
+    pub async fn scopes(&self, token: &str) -> Result<Claims, RestError> {
+        self.renew();
 
         let jwks = self.keys().await?;
-        let header = decode_header(token[1])?;
+        let header = decode_header(token)?;
         let kid = match header.kid {
             Some(k) => k,
             None => {
@@ -193,46 +332,143 @@ impl JwksKeys {
             }
         };
 
-        if let Some(j) = jwks.find(&kid) {
-            match j.algorithm {
-                AlgorithmParameters::RSA(ref rsa) => {
-                    let decoding_key = match DecodingKey::from_rsa_components(&rsa.n, &rsa.e) {
-                        Ok(k) => k,
-                        Err(e) => {
-                            log::trace!("\"Error decoding key: {}\"", e);
-                            return Err(RestError::JwtDecode);
-                        }
-                    };
-                    let algo = j.common.algorithm.expect("missing algorithm");
-                    let mut validation = Validation::new(algo);
-
-                    validation.validate_exp = true;
-                    validation.validate_nbf = true;
-                    validation.set_audience(&[&self.audience.clone().unwrap()]);
-
-                    log::trace!("Attempting to decode token");
-                    let decoded_token = match decode::<Claims>(
-                        token[1],
-                        &decoding_key,
-                        &validation,
-                    ) {
-                        Ok(e) => Ok(e),
-                        Err(e) => {
-                            log::debug!("Unable to decode token: {}", e);
-                            Err(e)
-                        }
-                    }?;
-                    log::trace!("decoded token: {:?}", decoded_token);
-
-                    log::debug!("\"sub={}, exp={}, scopes={:?}\"", decoded_token.claims.sub, decoded_token.claims.exp, decoded_token.claims.scp);
-                    Ok(decoded_token.claims)
+        let j = match jwks.find(&kid) {
+            Some(j) => j,
+            None => {
+                log::warn!("\"No matching JWK found for the given kid\"");
+                return Err(RestError::JwtDecode);
+            }
+        };
+
+        let decoding_key = decoding_key(j)?;
+        let algo = algorithm(j, header.alg)?;
+        let mut validation = Validation::new(algo);
+
+        validation.validate_exp = true;
+        validation.validate_nbf = true;
+        validation.set_audience(&self.audiences);
+        if let Some(ref issuer) = self.issuer {
+            validation.set_issuer(&[issuer]);
+        };
+
+        // Only claims that are present get validated, so make sure the ones we check are there
+        let mut required = vec!["exp", "aud"];
+        if self.issuer.is_some() {
+            required.push("iss");
+        };
+        validation.set_required_spec_claims(&required);
+
+        log::trace!("Attempting to decode token");
+        let decoded_token = match decode::<Value>(
+            token,
+            &decoding_key,
+            &validation,
+        ) {
+            Ok(e) => Ok(e),
+            Err(e) => {
+                log::debug!("Unable to decode token: {}", e);
+                Err(e)
+            }
+        }?;
+        log::trace!("decoded token: {:?}", decoded_token);
+
+        let claims = self.claims.claims(&decoded_token.claims)?;
+        log::debug!("\"sub={}, exp={:?}, scopes={:?}\"", claims.sub, claims.exp, claims.scp);
+        Ok(claims)
+    }
+}
+
+// The iss claim, read before the signature is checked so we know whose keys to check it with
+fn unverified_issuer(token: &str) -> Option<String> {
+    let payload = token.split('.').nth(1)?;
+    let payload = base64::decode_config(payload, base64::URL_SAFE_NO_PAD).ok()?;
+    let payload: Value = serde_json::from_slice(&payload).ok()?;
+    payload["iss"].as_str().map(|iss| iss.to_string())
+}
+
+// Skips keys we can't use, like encryption keys, rather than rejecting the whole set
+fn key_set(jwks: &Value) -> MyResult<jwk::JwkSet> {
+    let keys = match jwks["keys"].as_array() {
+        Some(keys) => keys
+            .iter()
+            .filter_map(|key| match serde_json::from_value::<jwk::Jwk>(key.clone()) {
+                Ok(key) => Some(key),
+                Err(e) => {
+                    log::debug!("\"Skipping unsupported JWK: {}\"", e);
+                    None
                 }
-                _ => Err(RestError::JwtDecode),
+            })
+            .collect(),
+        None => return Err(RestError::JwtDecode),
+    };
+    let j = jwk::JwkSet { keys };
+    log::trace!("keys: {:?}", j);
+    Ok(j)
+}
+
+// Public key for verifying signatures, from an RSA, EC (P-256/P-384) or OKP (Ed25519) JWK
+fn decoding_key(jwk: &jwk::Jwk) -> MyResult<DecodingKey> {
+    let key = match jwk.algorithm {
+        AlgorithmParameters::RSA(ref rsa) => DecodingKey::from_rsa_components(&rsa.n, &rsa.e),
+        AlgorithmParameters::EllipticCurve(ref ec) => match ec.curve {
+            EllipticCurve::P256 | EllipticCurve::P384 => {
+                DecodingKey::from_ec_components(&ec.x, &ec.y)
             }
-        } else {
-            log::warn!("\"No matching JWK found for the given kid\"");
-            Err(RestError::JwtDecode)
+            ref curve => {
+                log::trace!("\"Unsupported EC curve: {:?}\"", curve);
+                return Err(RestError::JwtDecode);
+            }
+        },
+        AlgorithmParameters::OctetKeyPair(ref okp) => match okp.curve {
+            EllipticCurve::Ed25519 => DecodingKey::from_ed_components(&okp.x),
+            ref curve => {
+                log::trace!("\"Unsupported OKP curve: {:?}\"", curve);
+                return Err(RestError::JwtDecode);
+            }
+        },
+        // Shared secrets have no business being in a public key set
+        AlgorithmParameters::OctetKey(_) => {
+            log::trace!("\"Refusing symmetric JWK\"");
+            return Err(RestError::JwtDecode);
+        }
+    };
+
+    key.map_err(|e| {
+        log::trace!("\"Error decoding key: {}\"", e);
+        RestError::JwtDecode
+    })
+}
+
+// The token's alg has to suit the key type, and match the JWK's alg when it declares one
+fn algorithm(jwk: &jwk::Jwk, algo: Algorithm) -> MyResult<Algorithm> {
+    if let Some(declared) = jwk.common.algorithm {
+        if declared != algo {
+            log::warn!("\"Token alg {:?} does not match JWK alg {:?}\"", algo, declared);
+            return Err(RestError::JwtDecode);
         }
+    };
+
+    let allowed = match (&jwk.algorithm, algo) {
+        (
+            AlgorithmParameters::RSA(_),
+            Algorithm::RS256
+            | Algorithm::RS384
+            | Algorithm::RS512
+            | Algorithm::PS256
+            | Algorithm::PS384
+            | Algorithm::PS512,
+        ) => true,
+        (AlgorithmParameters::EllipticCurve(ec), Algorithm::ES256) => ec.curve == EllipticCurve::P256,
+        (AlgorithmParameters::EllipticCurve(ec), Algorithm::ES384) => ec.curve == EllipticCurve::P384,
+        (AlgorithmParameters::OctetKeyPair(_), Algorithm::EdDSA) => true,
+        _ => false,
+    };
+
+    if allowed {
+        Ok(algo)
+    } else {
+        log::warn!("\"Token alg {:?} can't be used with this JWK\"", algo);
+        Err(RestError::JwtDecode)
     }
 }
 
@@ -242,7 +478,7 @@ pub async fn auth<B>(
     next: Next<B>,
 ) -> Result<Response, StatusCode> {
     if state.noauth {
-        req.extensions_mut().insert(AuthorizeScope::default());
+        req.extensions_mut().insert(state.anonymous());
         return Ok(next.run(req).await);
     }
 
@@ -271,3 +507,57 @@ pub async fn auth<B>(
     req.extensions_mut().insert(scopes);
     Ok(next.run(req).await)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jwk(key: Value) -> jwk::Jwk {
+        serde_json::from_value(key).unwrap()
+    }
+
+    fn rsa(alg: Option<&str>) -> jwk::Jwk {
+        let mut key = json!({"kty": "RSA", "kid": "rsa", "n": "AQAB", "e": "AQAB"});
+        if let Some(alg) = alg {
+            key["alg"] = json!(alg);
+        };
+        jwk(key)
+    }
+
+    fn ec(crv: &str) -> jwk::Jwk {
+        jwk(json!({"kty": "EC", "kid": "ec", "crv": crv, "x": "AQAB", "y": "AQAB"}))
+    }
+
+    #[test]
+    fn algorithm_has_to_suit_the_key_type() {
+        assert!(algorithm(&ec("P-256"), Algorithm::RS256).is_err());
+        assert!(algorithm(&rsa(None), Algorithm::ES256).is_err());
+        assert!(algorithm(&rsa(None), Algorithm::HS256).is_err());
+        assert!(algorithm(&ec("P-256"), Algorithm::ES256).is_ok());
+    }
+
+    #[test]
+    fn algorithm_has_to_suit_the_curve() {
+        assert!(algorithm(&ec("P-384"), Algorithm::ES256).is_err());
+        assert!(algorithm(&ec("P-256"), Algorithm::ES384).is_err());
+        assert!(algorithm(&ec("P-384"), Algorithm::ES384).is_ok());
+    }
+
+    #[test]
+    fn algorithm_falls_back_to_the_header() {
+        // Without an alg on the key, the header's is used when the key type agrees
+        assert_eq!(algorithm(&rsa(None), Algorithm::PS256).unwrap(), Algorithm::PS256);
+        assert!(algorithm(&rsa(None), Algorithm::EdDSA).is_err());
+
+        // With one, the header has to match it
+        assert_eq!(algorithm(&rsa(Some("RS256")), Algorithm::RS256).unwrap(), Algorithm::RS256);
+        assert!(algorithm(&rsa(Some("RS256")), Algorithm::RS512).is_err());
+    }
+
+    #[test]
+    fn decoding_key_refuses_unusable_keys() {
+        assert!(decoding_key(&jwk(json!({"kty": "oct", "k": "c2VjcmV0"}))).is_err());
+        assert!(decoding_key(&ec("P-521")).is_err());
+        assert!(decoding_key(&rsa(None)).is_ok());
+    }
+}
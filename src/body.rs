@@ -0,0 +1,197 @@
+use axum::{
+    async_trait,
+    body::{Bytes, HttpBody},
+    extract::{FromRequest, FromRequestParts, Query},
+    http::{header::CONTENT_TYPE, request::Parts, HeaderMap, Request, Uri},
+    response::{IntoResponse, Response},
+    BoxError, Json,
+};
+use bson::{Bson, Document};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::Value;
+
+use crate::error::Error as RestError;
+
+pub const BSON_CONTENT_TYPE: &str = "application/bson";
+pub const EJSON_CONTENT_TYPE: &str = "application/ejson";
+
+// Request body sent as either json, raw bson with Content-Type: application/bson,
+// or strict extended json with Content-Type: application/ejson or ?input=ejson
+#[derive(Debug, Clone)]
+pub struct Payload<T>(pub T);
+
+// Multiple documents sent as either a json or extended json array, or back to back bson documents
+#[derive(Debug, Clone)]
+pub struct Documents(pub Vec<Document>);
+
+#[async_trait]
+impl<T, S, B> FromRequest<S, B> for Payload<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<BoxError>,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request<B>, state: &S) -> Result<Self, Self::Rejection> {
+        let input = input(req.headers(), req.uri()).map_err(IntoResponse::into_response)?;
+        if input == Input::Json {
+            let Json(payload) = Json::<T>::from_request(req, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+            return Ok(Payload(payload));
+        }
+
+        let bytes = Bytes::from_request(req, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+
+        if input == Input::Ejson {
+            let payload = from_ejson(&bytes).map_err(IntoResponse::into_response)?;
+            return Ok(Payload(payload));
+        }
+
+        let mut docs = read_documents(&bytes).map_err(IntoResponse::into_response)?;
+        if docs.len() != 1 {
+            return Err(
+                RestError::BadRequest("Expected a single bson document".to_string())
+                    .into_response(),
+            );
+        }
+
+        match bson::from_document(docs.remove(0)) {
+            Ok(payload) => Ok(Payload(payload)),
+            Err(e) => Err(RestError::BadRequest(e.to_string()).into_response()),
+        }
+    }
+}
+
+#[async_trait]
+impl<S, B> FromRequest<S, B> for Documents
+where
+    S: Send + Sync,
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<BoxError>,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request<B>, state: &S) -> Result<Self, Self::Rejection> {
+        let input = input(req.headers(), req.uri()).map_err(IntoResponse::into_response)?;
+        if input == Input::Json {
+            let Json(docs) = Json::<Vec<Document>>::from_request(req, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+            return Ok(Documents(docs));
+        }
+
+        let bytes = Bytes::from_request(req, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+
+        let docs = match input {
+            Input::Ejson => from_ejson(&bytes),
+            _ => read_documents(&bytes),
+        };
+        docs.map(Documents).map_err(IntoResponse::into_response)
+    }
+}
+
+// How a request body is encoded, for endpoints that read the body themselves
+#[derive(Deserialize, PartialEq, Clone, Copy, Debug)]
+pub enum Input {
+    #[serde(rename = "json")]
+    Json,
+    #[serde(rename = "ejson")]
+    Ejson,
+    // Only picked by Content-Type
+    #[serde(skip)]
+    Bson,
+}
+
+// Read on its own, so endpoints' query options don't have to know about it
+#[derive(Deserialize)]
+struct QueriesInput {
+    input: Option<Input>,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Input
+where
+    S: Send + Sync,
+{
+    type Rejection = RestError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        input(&parts.headers, &parts.uri)
+    }
+}
+
+fn input(headers: &HeaderMap, uri: &Uri) -> Result<Input, RestError> {
+    if content_type(headers).is_some_and(|value| value.starts_with(BSON_CONTENT_TYPE)) {
+        return Ok(Input::Bson);
+    }
+
+    let queries = Query::<QueriesInput>::try_from_uri(uri)
+        .map_err(|e| RestError::BadRequest(e.body_text()))?;
+
+    if queries.0.input == Some(Input::Ejson)
+        || content_type(headers).is_some_and(|value| value.starts_with(EJSON_CONTENT_TYPE))
+    {
+        Ok(Input::Ejson)
+    } else {
+        Ok(Input::Json)
+    }
+}
+
+fn content_type(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+}
+
+// Every value goes through extended json conversion, in either canonical or relaxed mode,
+// so malformed or mixed type wrappers like {"$oid": "..", "x": 1} are rejected instead of
+// silently becoming documents
+fn from_ejson<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, RestError> {
+    ejson(bytes).map_err(RestError::BadRequest)
+}
+
+// The same, with the reason a value was refused as it is, for errors reported per line
+pub fn ejson<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, String> {
+    let value = serde_json::from_slice::<Value>(bytes)
+        .map_err(|e| format!("Invalid extended JSON: {}", e))?;
+
+    let bson = Bson::try_from(value).map_err(|e| format!("Invalid extended JSON: {}", e))?;
+
+    bson::from_bson(bson).map_err(|e| e.to_string())
+}
+
+// Each bson document starts with its own little endian i32 length
+fn read_documents(mut bytes: &[u8]) -> Result<Vec<Document>, RestError> {
+    let mut docs = Vec::new();
+
+    while !bytes.is_empty() {
+        let len = match bytes.get(..4) {
+            Some(len) => i32::from_le_bytes([len[0], len[1], len[2], len[3]]),
+            None => return Err(RestError::BadRequest("Truncated bson document".to_string())),
+        };
+
+        if len < 5 || len as usize > bytes.len() {
+            return Err(RestError::BadRequest(
+                "Invalid bson document length".to_string(),
+            ));
+        }
+
+        let (doc, rest) = bytes.split_at(len as usize);
+        match Document::from_reader(doc) {
+            Ok(doc) => docs.push(doc),
+            Err(e) => return Err(RestError::BadRequest(e.to_string())),
+        }
+        bytes = rest;
+    }
+
+    Ok(docs)
+}
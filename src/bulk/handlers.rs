@@ -0,0 +1,32 @@
+use axum::{
+    extract::{Path, Query},
+    Extension, Json,
+};
+use serde_json::{json, Value};
+
+use crate::body::Payload;
+use crate::bulk::structs::WriteModel;
+use crate::error::Error as RestError;
+use crate::insert::structs::CustomInsertManyOptions;
+use crate::queries::QueriesConcern;
+use crate::scopes::AuthorizeScope;
+use crate::sessions::Transaction;
+use crate::State;
+
+pub async fn bulk_write(
+    Extension(state): Extension<State>,
+    Extension(scopes): Extension<AuthorizeScope>,
+    Path((db, coll)): Path<(String, String)>,
+    transaction: Transaction,
+    queries: Query<CustomInsertManyOptions>,
+    concern: Query<QueriesConcern>,
+    Payload(body): Payload<Vec<WriteModel>>,
+) -> Result<Json<Value>, RestError> {
+    // Validate that the client has access to this database
+    scopes.write(&db, Some(&coll))?;
+
+    log::info!("{{\"fn\": \"bulk_write\", \"db\": \"{}\", \"coll\": \"{}\"}}", &db, &coll);
+    Ok(Json(json!(
+        state.db.bulk_write(&db, &coll, body, queries, &concern, transaction, &scopes).await?
+    )))
+}
@@ -0,0 +1,44 @@
+use bson::Document;
+use mongodb::options::{Collation, Hint, UpdateModifications};
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub enum WriteModel {
+    InsertOne {
+        document: Document,
+    },
+    UpdateOne {
+        filter: Document,
+        update: UpdateModifications,
+        upsert: Option<bool>,
+        array_filters: Option<Vec<Document>>,
+        collation: Option<Collation>,
+        hint: Option<Hint>,
+    },
+    UpdateMany {
+        filter: Document,
+        update: UpdateModifications,
+        upsert: Option<bool>,
+        array_filters: Option<Vec<Document>>,
+        collation: Option<Collation>,
+        hint: Option<Hint>,
+    },
+    ReplaceOne {
+        filter: Document,
+        replacement: Document,
+        upsert: Option<bool>,
+        collation: Option<Collation>,
+        hint: Option<Hint>,
+    },
+    DeleteOne {
+        filter: Document,
+        collation: Option<Collation>,
+        hint: Option<Hint>,
+    },
+    DeleteMany {
+        filter: Document,
+        collation: Option<Collation>,
+        hint: Option<Hint>,
+    },
+}
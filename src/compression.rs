@@ -0,0 +1,49 @@
+use axum::http::{header::CACHE_CONTROL, Extensions, HeaderMap, StatusCode, Version};
+use axum::BoxError;
+use clap::ValueEnum;
+use tower_http::compression::{
+    predicate::{NotForContentType, Predicate, SizeAbove},
+    CompressionLayer,
+};
+
+use crate::error::Error as RestError;
+use crate::framing::PARQUET_CONTENT_TYPE;
+
+// Response encodings offered to clients through Accept-Encoding
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum Algorithm {
+    Gzip,
+    Br,
+    Zstd,
+}
+
+// Compress responses larger than min_size, along with streams, which have no known size
+// Parquet pages are already compressed, so they are left alone
+pub fn layer(algorithms: &[Algorithm], min_size: u16) -> CompressionLayer<impl Predicate> {
+    let predicate = SizeAbove::new(min_size)
+        .and(NotForContentType::GRPC)
+        .and(NotForContentType::IMAGES)
+        .and(NotForContentType::const_new("text/event-stream"))
+        .and(NotForContentType::const_new(PARQUET_CONTENT_TYPE))
+        .and(transformable);
+
+    CompressionLayer::new()
+        .gzip(algorithms.contains(&Algorithm::Gzip))
+        .br(algorithms.contains(&Algorithm::Br))
+        .zstd(algorithms.contains(&Algorithm::Zstd))
+        .compress_when(predicate)
+}
+
+// Encoders buffer their output, so live streams like change streams opt out with no-transform
+fn transformable(_: StatusCode, _: Version, headers: &HeaderMap, _: &Extensions) -> bool {
+    !headers
+        .get_all(CACHE_CONTROL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .any(|value| value.split(',').any(|directive| directive.trim() == "no-transform"))
+}
+
+// Request decompression can only fail by passing along an error from the handler
+pub async fn decompression_error(e: BoxError) -> RestError {
+    RestError::BadRequest(e.to_string())
+}
@@ -0,0 +1,157 @@
+use bson::{oid::ObjectId, Document};
+use chrono::Utc;
+use core::time::Duration;
+use mongodb::Cursor;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::Mutex as AsyncMutex;
+
+use crate::error::Error as RestError;
+use crate::scopes::Owner;
+
+type Result<T> = std::result::Result<T, RestError>;
+
+#[derive(Debug)]
+pub struct HeldCursor {
+    owner: Owner,
+    namespace: String,
+    last_used: i64,
+    pending: bool,
+    cursor: Cursor<Document>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Cursors {
+    cursors: Arc<Mutex<HashMap<String, Arc<AsyncMutex<HeldCursor>>>>>,
+    idle_timeout: i64,
+}
+
+impl Cursors {
+    pub fn new(idle_timeout: u64) -> Self {
+        Cursors {
+            cursors: Arc::new(Mutex::new(HashMap::new())),
+            idle_timeout: idle_timeout as i64,
+        }
+    }
+
+    // Read the first page from a new cursor, holding on to the cursor if more docs remain
+    pub async fn start(
+        &self,
+        cursor: Cursor<Document>,
+        owner: &Owner,
+        namespace: &str,
+        page_size: usize,
+    ) -> Result<(Vec<Document>, Option<String>)> {
+        let mut held = HeldCursor {
+            owner: owner.clone(),
+            namespace: namespace.to_string(),
+            last_used: Utc::now().timestamp(),
+            pending: false,
+            cursor,
+        };
+
+        let (docs, exhausted) = held.page(page_size).await?;
+        if exhausted {
+            return Ok((docs, None));
+        }
+
+        let id = ObjectId::new().to_hex();
+
+        log::debug!("\"{}, Holding cursor {} on {}\"", owner, id, namespace);
+        self.cursors
+            .lock()
+            .unwrap()
+            .insert(id.clone(), Arc::new(AsyncMutex::new(held)));
+
+        Ok((docs, Some(id)))
+    }
+
+    // Read the next page from a held cursor, dropping the cursor once it is exhausted
+    pub async fn next(
+        &self,
+        id: &str,
+        owner: &Owner,
+        namespace: &str,
+        page_size: usize,
+    ) -> Result<(Vec<Document>, Option<String>)> {
+        let held = match self.cursors.lock().unwrap().get(id) {
+            Some(held) => held.clone(),
+            None => {
+                log::debug!("Cursor {} not found", id);
+                return Err(RestError::CursorNotFound);
+            }
+        };
+
+        let mut held = held.lock().await;
+        if &held.owner != owner || held.namespace != namespace {
+            log::warn!("\"{}, cursor={}, action=reject\"", owner, id);
+            return Err(RestError::UnauthorizedClient);
+        }
+
+        held.last_used = Utc::now().timestamp();
+        let result = held.page(page_size).await;
+
+        match result {
+            Ok((docs, false)) => Ok((docs, Some(id.to_string()))),
+            Ok((docs, true)) => {
+                log::debug!("\"{}, Cursor {} is exhausted\"", owner, id);
+                self.cursors.lock().unwrap().remove(id);
+                Ok((docs, None))
+            }
+            Err(e) => {
+                self.cursors.lock().unwrap().remove(id);
+                Err(e)
+            }
+        }
+    }
+
+    // Kick off background job to drop cursors that have sat idle for too long
+    pub fn reap(&self) {
+        let me = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(5));
+            loop {
+                interval.tick().await;
+                me.expire();
+            }
+        });
+    }
+
+    fn expire(&self) {
+        let now = Utc::now().timestamp();
+        let mut cursors = self.cursors.lock().unwrap();
+
+        // Cursors currently in use are never idle, dropping the rest kills them on the server
+        cursors.retain(|id, held| match held.try_lock() {
+            Ok(held) if now - held.last_used >= self.idle_timeout => {
+                log::info!("\"{}, Cursor {} has expired\"", held.owner, id);
+                false
+            }
+            _ => true,
+        });
+    }
+}
+
+impl HeldCursor {
+    // Returns a page of docs, and whether the cursor has been exhausted
+    async fn page(&mut self, page_size: usize) -> Result<(Vec<Document>, bool)> {
+        let mut docs = Vec::new();
+
+        // Pick up the doc we peeked at while reading the last page
+        if self.pending {
+            docs.push(self.cursor.deserialize_current()?);
+            self.pending = false;
+        }
+
+        while docs.len() < page_size {
+            if !self.cursor.advance().await? {
+                return Ok((docs, true));
+            }
+            docs.push(self.cursor.deserialize_current()?);
+        }
+
+        // Peek ahead so the last page doesn't hand out a token for an empty page
+        self.pending = self.cursor.advance().await?;
+        Ok((docs, !self.pending))
+    }
+}
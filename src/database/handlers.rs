@@ -11,7 +11,7 @@ pub async fn rs_status(
     Extension(scopes): Extension<AuthorizeScope>,
 ) -> Result<Json<Value>, RestError> {
     // Validate that the client has access
-    scopes.monitor(&"admin")?;
+    scopes.monitor(&"admin", None)?;
 
     log::info!("{{\"fn\": \"rs_status\"}}");
 
@@ -27,7 +27,7 @@ pub async fn host_info(
     Extension(scopes): Extension<AuthorizeScope>,
 ) -> Result<Json<Value>, RestError> {
     // Validate that the client has access
-    scopes.monitor(&"admin")?;
+    scopes.monitor(&"admin", None)?;
 
     log::info!("{{\"fn\": \"lock_info\"}}");
 
@@ -43,7 +43,7 @@ pub async fn rs_config(
     Extension(scopes): Extension<AuthorizeScope>,
 ) -> Result<Json<Value>, RestError> {
     // Validate that the client has access
-    scopes.monitor(&"admin")?;
+    scopes.monitor(&"admin", None)?;
 
     log::info!("{{\"fn\": \"lock_info\"}}");
 
@@ -59,7 +59,7 @@ pub async fn lock_info(
     Extension(scopes): Extension<AuthorizeScope>,
 ) -> Result<Json<Value>, RestError> {
     // Validate that the client has access
-    scopes.monitor(&"admin")?;
+    scopes.monitor(&"admin", None)?;
 
     log::info!("{{\"fn\": \"lock_info\"}}");
 
@@ -75,7 +75,7 @@ pub async fn rs_log(
     Extension(scopes): Extension<AuthorizeScope>,
 ) -> Result<Json<Value>, RestError> {
     // Validate that the client has access
-    scopes.monitor(&"admin")?;
+    scopes.monitor(&"admin", None)?;
 
     log::info!("{{\"fn\": \"rs_log\"}}");
     let payload = doc! { "getLog": "global"};
@@ -90,7 +90,7 @@ pub async fn rs_operations(
     Extension(scopes): Extension<AuthorizeScope>,
 ) -> Result<Json<Value>, RestError> {
     // Validate that the client has access
-    scopes.monitor(&"admin")?;
+    scopes.monitor(&"admin", None)?;
 
     log::info!("{{\"fn\": \"rs_operations\"}}");
     let payload = doc! { "currentOp": 1};
@@ -124,7 +124,7 @@ pub async fn rs_stats(
     Extension(scopes): Extension<AuthorizeScope>,
 ) -> Result<Json<Value>, RestError> {
     // Validate that the client has access
-    scopes.monitor(&"admin")?;
+    scopes.monitor(&"admin", None)?;
 
     log::info!("{{\"fn\": \"rs_stats\"}}");
     let payload = doc! { "serverStatus": 1};
@@ -138,7 +138,7 @@ pub async fn rs_top(
     Extension(scopes): Extension<AuthorizeScope>,
 ) -> Result<Json<Value>, RestError> {
     // Validate that the client has access
-    scopes.monitor(&"admin")?;
+    scopes.monitor(&"admin", None)?;
 
     log::info!("{{\"fn\": \"rs_top\"}}");
     let payload = doc! { "top": 1};
@@ -152,7 +152,7 @@ pub async fn rs_conn(
     Extension(scopes): Extension<AuthorizeScope>,
 ) -> Result<Json<Value>, RestError> {
     // Validate that the client has access
-    scopes.monitor(&"admin")?;
+    scopes.monitor(&"admin", None)?;
 
     log::info!("{{\"fn\": \"rs_conn\"}}");
     let payload = doc! { "connectionStatus": 1};
@@ -166,7 +166,7 @@ pub async fn rs_pool(
     Extension(scopes): Extension<AuthorizeScope>,
 ) -> Result<Json<Value>, RestError> {
     // Validate that the client has access
-    scopes.monitor(&"admin")?;
+    scopes.monitor(&"admin", None)?;
 
     log::info!("{{\"fn\": \"rs_pool\"}}");
     let payload = doc! { "connPoolStats": 1};
@@ -181,7 +181,7 @@ pub async fn db_stats(
     Path(db): Path<String>,
 ) -> Result<Json<Value>, RestError> {
     // Validate that the client has access
-    scopes.monitor(&db)?;
+    scopes.monitor(&db, None)?;
 
     log::info!("{{\"fn\": \"db_stats\", \"db\": \"{}\"}}", &db);
     let payload = doc! { "dbStats": 1};
@@ -196,7 +196,7 @@ pub async fn coll_stats(
     Path((db, coll)): Path<(String, String)>,
 ) -> Result<Json<Value>, RestError> {
     // Validate that the client has access
-    scopes.monitor(&db)?;
+    scopes.monitor(&db, Some(&coll))?;
 
     log::info!("{{\"fn\": \"coll_stats\", \"db\": \"{}\", \"coll\": \"{}\"}}", &db, &coll);
     let payload = doc! { "collStats": coll};
@@ -213,7 +213,7 @@ pub async fn databases(
     log::info!("{{\"fn\": \"databases\"}}");
 
     // This needs to check for admin monitor, and if that fails, return the db's the client has access to
-    if scopes.read(&"admin").is_ok() {
+    if scopes.read(&"admin", None).is_ok() {
         Ok(Json(json!(state.db.databases().await?)))
     } else {
         Ok(Json(json!(scopes.authorized_dbs())))
@@ -226,7 +226,7 @@ pub async fn db_colls(
     Path(db): Path<String>,
 ) -> Result<Json<Value>, RestError> {
     // Validate that the client has access
-    scopes.read(&db)?;
+    scopes.read(&db, None)?;
 
     log::info!("{{\"fn\": \"db_colls\", \"db\": \"{}\"}}", &db);
     Ok(Json(json!(state.db.collections(&db).await?)))
@@ -238,7 +238,7 @@ pub async fn coll_count(
     Path((db, coll)): Path<(String, String)>,
 ) -> Result<Json<Value>, RestError> {
     // Validate that the client has access
-    scopes.read(&db)?;
+    scopes.read(&db, Some(&coll))?;
 
     log::info!("{{\"fn\": \"coll_count\", \"db\": \"{}\", \"coll\": \"{}\"}}", &db, &coll);
     Ok(Json(json!(state.db.coll_count(&db, &coll).await?)))
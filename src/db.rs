@@ -1,48 +1,107 @@
 use crate::error::Error as RestError;
 use axum::body::Bytes;
-use axum::body::StreamBody;
-use axum::extract::Query;
+use axum::http::{header::CACHE_CONTROL, HeaderName};
+use axum::response::{IntoResponse, Response};
+use axum::extract::{BodyStream, Query};
 use core::time::Duration;
 use chrono::Utc;
-use futures::stream::StreamExt;
-use futures::Stream;
+use futures::stream::{Stream, StreamExt};
 use mongodb::bson::{doc, document::Document, to_bson, to_document};
 use mongodb::options::IndexOptions;
-use mongodb::IndexModel;
+use mongodb::error::ErrorKind;
+use mongodb::change_stream::{event::ChangeStreamEvent, ChangeStream};
+use mongodb::{Collection, IndexModel};
 use mongodb::{
-    options::ClientOptions, options::InsertManyOptions, options::InsertOneOptions,
-    options::ListDatabasesOptions, Client,
+    options::ClientOptions, options::DeleteOptions, options::InsertManyOptions,
+    options::InsertOneOptions, options::ListDatabasesOptions, options::ReplaceOptions,
+    options::TransactionOptions, options::UpdateOptions, Client,
 };
 use serde::Serialize;
 use serde_json::{json, Value};
 
 use crate::aggregate::structs::Aggregate;
-use crate::delete::structs::DeleteOne;
+use crate::bulk::structs::WriteModel;
+use crate::body::{self, Input};
+use crate::cursors::Cursors;
+use crate::delete::structs::{DeleteOne, FindOneAndDelete};
+use crate::document::structs::{DocumentId, QueriesUpsert};
+use crate::export::{self, Output};
+use crate::framing::{self, Framing, LastEventId};
 use crate::find::structs::{Distinct, Find, FindOne, Count};
 use crate::index::structs::Index;
 use crate::insert::structs::{CustomInsertManyOptions, CustomInsertOneOptions};
-use crate::queries::{Formats, QueriesDelete, QueriesFormat};
-use crate::update::structs::Update;
-use crate::watch::structs::Watch;
+use crate::queries::{
+    concerns, Formats, QueriesConcern, QueriesDelete, QueriesFormat, QueriesImport, QueriesPage,
+    QueriesWatch,
+};
+use crate::redaction::{Redaction, Redactions};
+use crate::schema::structs::{QueriesSchema, Schema};
+use crate::scopes::{AuthorizeScope, Owner};
+use crate::sessions::{with_session, Session, Sessions, Transaction};
+use crate::update::structs::{FindOneAndReplace, FindOneAndUpdate, Replace, Update};
+use crate::watch::structs::{Watch, WatchEvent};
 
 #[derive(Clone, Debug)]
 pub struct DB {
     pub client: Client,
     pub readonly: bool,
+    pub sessions: Sessions,
+    pub cursors: Cursors,
 }
 
 type Result<T> = std::result::Result<T, RestError>;
 
+// Imports skip the body limit, so each line is held to the same 16MB instead
+const MAX_IMPORT_LINE: usize = 16777216;
+
 impl DB {
-    pub async fn init(mut client_options: ClientOptions, readonly: bool) -> Result<Self> {
+    pub async fn init(
+        mut client_options: ClientOptions,
+        readonly: bool,
+        transaction_timeout: u64,
+        cursor_timeout: u64,
+    ) -> Result<Self> {
         client_options.app_name = Some("mongodb-rest-rs".to_string());
 
+        let sessions = Sessions::new(transaction_timeout);
+        sessions.reap();
+
+        let cursors = Cursors::new(cursor_timeout);
+        cursors.reap();
+
         Ok(Self {
             client: Client::with_options(client_options)?,
             readonly,
+            sessions,
+            cursors,
         })
     }
 
+    pub async fn start_transaction(
+        &self,
+        owner: &Owner,
+        options: Option<TransactionOptions>,
+    ) -> Result<Value> {
+        if self.readonly {
+            return Err(RestError::ReadOnly);
+        }
+
+        log::debug!("Starting transaction");
+
+        let id = self.sessions.start(&self.client, owner, options).await?;
+        Ok(json!({ "transaction": id }))
+    }
+
+    pub async fn commit_transaction(&self, id: &str, owner: &Owner) -> Result<Value> {
+        self.sessions.commit(id, owner).await?;
+        Ok(json!({ "message": "committed transaction", "transaction": id }))
+    }
+
+    pub async fn abort_transaction(&self, id: &str, owner: &Owner) -> Result<Value> {
+        self.sessions.abort(id, owner).await?;
+        Ok(json!({ "message": "aborted transaction", "transaction": id }))
+    }
+
     pub async fn rs_set(&self) -> Result<Option<String>> {
         let payload = doc! { "isMaster": 1};
 
@@ -134,81 +193,261 @@ impl DB {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn watch(
         &self,
         database: &str,
         collection: &str,
         payload: Watch,
-        queries: Query<QueriesFormat>,
-    ) -> Result<StreamBody<impl Stream<Item = Result<Bytes>>>> {
+        queries: Query<QueriesWatch>,
+        framing: Framing,
+        last_event_id: LastEventId,
+        scopes: &AuthorizeScope,
+    ) -> Result<Response> {
+        log::debug!("Watching {}.{}", database, collection);
+
+        scopes.redaction(database, collection).watch(&payload.pipeline)?;
+
         let collection = self
             .client
             .database(&database)
             .collection::<Document>(collection);
 
+        let payload = Self::resume(payload, &queries, last_event_id)?;
         let cursor = collection.watch(payload.pipeline, payload.options).await?;
 
-        // This was the simplest way to get this to work
-        // Trying to map the items to Bytes did not work, and would cause the connection to drop
-        // However, get'ing a single field from the ChangeStream doc would work, only if the var was to_owned()
-        // However, I couldn't get the full document to persist
+        Ok(Self::change_events(cursor, queries, framing, scopes.redactions()))
+    }
 
-        Ok(StreamBody::new(cursor.map(move |d| match d {
-            Ok(o) => {
-                let bson = match queries.clone().format {
-                    None | Some(Formats::Json) => to_bson(&o)?.into_relaxed_extjson(),
-                    Some(Formats::Ejson) => to_bson(&o)?.into_canonical_extjson(),
-                };
-                log::debug!("Change stream event: {:?}", bson);
-                let string = format!("{}\n", bson);
-                Ok(string.into())
-            }
-            Err(e) => {
-                log::error!("Error in change stream: {}", e);
-                Ok(format!("{{\"error\":\"{}\"}}", e.to_string().replace('"', "\\\"")).into())
+    pub async fn watch_database(
+        &self,
+        database: &str,
+        payload: Watch,
+        queries: Query<QueriesWatch>,
+        framing: Framing,
+        last_event_id: LastEventId,
+        scopes: &AuthorizeScope,
+    ) -> Result<Response> {
+        log::debug!("Watching {}", database);
+
+        scopes.redactions().database(Some(database)).watch(&payload.pipeline)?;
+
+        let payload = Self::resume(payload, &queries, last_event_id)?;
+        let cursor = self
+            .client
+            .database(database)
+            .watch(payload.pipeline, payload.options)
+            .await?;
+
+        Ok(Self::change_events(cursor, queries, framing, scopes.redactions()))
+    }
+
+    pub async fn watch_cluster(
+        &self,
+        mut payload: Watch,
+        queries: Query<QueriesWatch>,
+        framing: Framing,
+        last_event_id: LastEventId,
+        databases: Option<Vec<String>>,
+        scopes: &AuthorizeScope,
+    ) -> Result<Response> {
+        log::debug!("Watching cluster");
+
+        scopes.redactions().database(None).watch(&payload.pipeline)?;
+
+        // Limit events to the databases the client can read, ahead of the client's own pipeline
+        if let Some(databases) = databases {
+            log::debug!("Limiting change stream to {:?}", databases);
+            payload
+                .pipeline
+                .insert(0, doc! {"$match": {"ns.db": {"$in": databases}}});
+        };
+
+        let payload = Self::resume(payload, &queries, last_event_id)?;
+        let cursor = self
+            .client
+            .watch(payload.pipeline, payload.options)
+            .await?;
+
+        Ok(Self::change_events(cursor, queries, framing, scopes.redactions()))
+    }
+
+    // Resume options from the query override the body, and reconnecting EventSource
+    // clients pick up right after the last event they saw
+    fn resume(
+        mut payload: Watch,
+        queries: &QueriesWatch,
+        last_event_id: LastEventId,
+    ) -> Result<Watch> {
+        let mut options = payload.options.unwrap_or_default();
+        options.resume_after = queries.resume_after()?.or(options.resume_after);
+        options.start_after = queries.start_after()?.or(options.start_after);
+        options.start_at_operation_time = queries
+            .start_at_operation_time()?
+            .or(options.start_at_operation_time);
+
+        // The server refuses more than one starting point, and the event id is the most recent
+        if let Some(token) = last_event_id.resume_token()? {
+            options.resume_after = Some(token);
+            options.start_after = None;
+            options.start_at_operation_time = None;
+        };
+
+        payload.options = Some(options);
+        Ok(payload)
+    }
+
+    fn change_events(
+        cursor: ChangeStream<ChangeStreamEvent<Document>>,
+        queries: Query<QueriesWatch>,
+        framing: Framing,
+        redactions: Redactions,
+    ) -> Response {
+        // Change streams are live, so proxies and the compression layer must not buffer them
+        const LIVE: [(HeaderName, &str); 1] = [(CACHE_CONTROL, "no-cache, no-transform")];
+
+        // The stream ends with the first error, which carries the last resume token seen
+        let events = cursor.scan((None, false), move |(resume_token, ended), d| {
+            if *ended {
+                return futures::future::ready(None);
             }
-        })))
+
+            let event = match d {
+                Ok(mut o) => {
+                    redactions.event(&mut o);
+                    *resume_token = Some(o.id.clone());
+                    WatchEvent::Change {
+                        resume_token: o.id.clone(),
+                        event: Box::new(o),
+                    }
+                }
+                Err(e) => {
+                    log::error!("Error in change stream: {}", e);
+                    *ended = true;
+                    WatchEvent::Error {
+                        resume_token: resume_token.clone(),
+                        error: e.to_string(),
+                    }
+                }
+            };
+
+            futures::future::ready(Some(event))
+        });
+
+        // Server-Sent Events carry the resume token as the event id
+        if framing == Framing::EventStream {
+            let stream = events.map(move |event| {
+                let (name, resume_token, data) = match event {
+                    WatchEvent::Change {
+                        ref resume_token,
+                        ref event,
+                    } => (None, Some(resume_token), to_bson(event)?),
+                    WatchEvent::Error {
+                        ref resume_token,
+                        ..
+                    } => (Some("error"), resume_token.as_ref(), to_bson(&event)?),
+                };
+
+                let id = match resume_token {
+                    Some(token) => Some(to_bson(token)?.into_relaxed_extjson().to_string()),
+                    None => None,
+                };
+                let data = match queries.format {
+                    Some(Formats::Ejson) => data.into_canonical_extjson(),
+                    _ => data.into_relaxed_extjson(),
+                };
+                log::debug!("Change stream event: {:?}", data);
+                Ok(framing::event(name, id.as_deref(), &data.to_string()))
+            });
+
+            return (LIVE, framing::events(stream)).into_response();
+        }
+
+        // Change events don't flatten into csv or record batches, so they are sent as json instead
+        let format = queries
+            .format
+            .clone()
+            .filter(|f| !matches!(f, Formats::Csv | Formats::Arrow | Formats::Parquet));
+
+        let stream = events.map(move |event| {
+            let bytes: Bytes = match queries.format {
+                None | Some(Formats::Json) | Some(Formats::Csv) | Some(Formats::Arrow) | Some(Formats::Parquet) => to_bson(&event)?.into_relaxed_extjson().to_string().into(),
+                Some(Formats::Ejson) => to_bson(&event)?.into_canonical_extjson().to_string().into(),
+                Some(Formats::Bson) => bson::to_vec(&event)?.into(),
+            };
+            log::debug!("Change stream event: {:?}", bytes);
+            Ok(bytes)
+        });
+
+        (LIVE, framing::respond(stream, &format, framing)).into_response()
     }
 
+    // Raw change stream, for callers that frame the events themselves
+    pub async fn change_stream(
+        &self,
+        database: &str,
+        collection: &str,
+        payload: Watch,
+        scopes: &AuthorizeScope,
+    ) -> Result<impl futures::Stream<Item = mongodb::error::Result<ChangeStreamEvent<Document>>> + Send + Unpin> {
+        log::debug!("Watching {}.{}", database, collection);
+
+        scopes.redaction(database, collection).watch(&payload.pipeline)?;
+
+        let collection = self
+            .client
+            .database(database)
+            .collection::<Document>(collection);
+
+        let redactions = scopes.redactions();
+        let stream = collection.watch(payload.pipeline, payload.options).await?;
+        Ok(stream.map(move |event| {
+            event.map(|mut event| {
+                redactions.event(&mut event);
+                event
+            })
+        }))
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub async fn aggregate(
         &self,
         database: &str,
         collection: &str,
         payload: Aggregate,
         queries: Query<QueriesFormat>,
-    ) -> Result<StreamBody<impl Stream<Item = Result<Bytes>>>> {
+        framing: Framing,
+        concern: &QueriesConcern,
+        scopes: &AuthorizeScope,
+    ) -> Result<Response> {
+        let pipeline = scopes.redactions().pipeline(database, collection, payload.pipeline)?;
+        let redaction = scopes.redaction(database, collection);
+
         let collection = self
             .client
             .database(&database)
             .collection::<Document>(collection);
 
-        let cursor = collection
-            .aggregate(payload.pipeline, payload.options)
-            .await?;
+        let mut options = payload.options.unwrap_or_default();
+        concerns!(options, concern, read, write);
 
-        let stream = cursor.map(move |d| match d {
-            Ok(o) => {
-                let bson = match queries.clone().format {
-                    None | Some(Formats::Json) => to_bson(&o)?.into_relaxed_extjson(),
-                    Some(Formats::Ejson) => to_bson(&o)?.into_canonical_extjson(),
-                };
-                log::debug!("Found doc: {:?}", bson);
-                let string = format!("{}\n", bson);
-                Ok(string.into())
-            }
-            Err(e) => Err(e)?,
-        });
+        let cursor = collection.aggregate(pipeline, options).await?;
+        let cursor = redaction.cursor(cursor);
 
-        Ok(StreamBody::new(stream))
+        Self::render(cursor, &queries, framing)
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn find(
         &self,
         database: &str,
         collection: &str,
         payload: Find,
         queries: Query<QueriesFormat>,
-    ) -> Result<StreamBody<impl Stream<Item = Result<Bytes>>>> {
+        framing: Framing,
+        concern: &QueriesConcern,
+        scopes: &AuthorizeScope,
+    ) -> Result<Response> {
         // Log which collection this is going into
         log::debug!("Searching {}.{}", database, collection);
 
@@ -217,22 +456,183 @@ impl DB {
             .database(database)
             .collection::<Document>(collection);
 
-        let cursor = collection.find(payload.filter, payload.options).await?;
+        let mut options = payload.options.unwrap_or_default();
+        concerns!(options, concern, read);
 
-        let stream = cursor.map(move |d| match d {
-            Ok(o) => {
-                let bson = match queries.clone().format {
-                    None | Some(Formats::Json) => to_bson(&o)?.into_relaxed_extjson(),
-                    Some(Formats::Ejson) => to_bson(&o)?.into_canonical_extjson(),
-                };
-                log::debug!("Found doc: {:?}", bson);
-                let string = format!("{}\n", bson);
-                Ok(string.into())
+        // Redacted fields are left out by the server where the projection allows, and by us otherwise
+        let redaction = scopes.redaction(database, collection.name());
+        options.projection = redaction.projection(options.projection)?;
+        redaction.filter(&payload.filter)?;
+        redaction.sort(&options.sort)?;
+        redaction.sort(&options.min)?;
+        redaction.sort(&options.max)?;
+
+        let cursor = collection.find(payload.filter, options).await?;
+        let cursor = redaction.cursor(cursor);
+
+        Self::render(cursor, &queries, framing)
+    }
+
+    // Streams docs from find and aggregate out in the requested format
+    fn render<S>(docs: S, queries: &QueriesFormat, framing: Framing) -> Result<Response>
+    where
+        S: Stream<Item = mongodb::error::Result<Document>> + Send + Unpin + 'static,
+    {
+        let response = match Output::new(queries)? {
+            Output::Rows(writer) => {
+                framing::respond(export::rows(docs, writer), &queries.format, framing).into_response()
             }
-            Err(e) => Err(e)?,
-        });
+            Output::Batches(writer) => {
+                let batches = export::record_batches(docs, *writer);
+                framing::respond(batches, &queries.format, framing).into_response()
+            }
+        };
+        Ok(response)
+    }
+
+    pub async fn find_page(
+        &self,
+        database: &str,
+        collection: &str,
+        payload: Find,
+        queries: &QueriesPage,
+        concern: &QueriesConcern,
+        scopes: &AuthorizeScope,
+    ) -> Result<Value> {
+        log::debug!("Searching {}.{} for a page", database, collection);
+        Formats::json_only(&queries.format)?;
+
+        let redaction = scopes.redaction(database, collection);
+
+        let namespace = format!("{}.{}", database, collection);
+
+        let collection = self
+            .client
+            .database(database)
+            .collection::<Document>(collection);
+
+        // Fetch a page at a time from the server unless asked otherwise
+        let mut options = payload.options.unwrap_or_default();
+        concerns!(options, concern, read);
+        if options.batch_size.is_none() {
+            options.batch_size = Some(queries.page_size);
+        };
+        options.projection = redaction.projection(options.projection)?;
+        redaction.filter(&payload.filter)?;
+        redaction.sort(&options.sort)?;
+        redaction.sort(&options.min)?;
+        redaction.sort(&options.max)?;
+
+        let cursor = collection.find(payload.filter, options).await?;
+        let (docs, id) = self
+            .cursors
+            .start(cursor, &scopes.owner(), &namespace, queries.page_size as usize)
+            .await?;
+
+        Self::page(docs, id, &queries.format, &redaction)
+    }
+
+    pub async fn aggregate_page(
+        &self,
+        database: &str,
+        collection: &str,
+        payload: Aggregate,
+        queries: &QueriesPage,
+        concern: &QueriesConcern,
+        scopes: &AuthorizeScope,
+    ) -> Result<Value> {
+        log::debug!("Aggregating {}.{} for a page", database, collection);
+        Formats::json_only(&queries.format)?;
+
+        let pipeline = scopes.redactions().pipeline(database, collection, payload.pipeline)?;
+        let redaction = scopes.redaction(database, collection);
+
+        let namespace = format!("{}.{}", database, collection);
+
+        let collection = self
+            .client
+            .database(database)
+            .collection::<Document>(collection);
+
+        // Fetch a page at a time from the server unless asked otherwise
+        let mut options = payload.options.unwrap_or_default();
+        concerns!(options, concern, read, write);
+        if options.batch_size.is_none() {
+            options.batch_size = Some(queries.page_size);
+        };
+
+        let cursor = collection.aggregate(pipeline, options).await?;
+        let (docs, id) = self
+            .cursors
+            .start(cursor, &scopes.owner(), &namespace, queries.page_size as usize)
+            .await?;
 
-        Ok(StreamBody::new(stream))
+        Self::page(docs, id, &queries.format, &redaction)
+    }
+
+    pub async fn cursor_next(
+        &self,
+        database: &str,
+        collection: &str,
+        id: &str,
+        queries: &QueriesPage,
+        scopes: &AuthorizeScope,
+    ) -> Result<Value> {
+        log::debug!("Getting next page from cursor {}", id);
+        Formats::json_only(&queries.format)?;
+
+        let namespace = format!("{}.{}", database, collection);
+
+        let (docs, id) = self
+            .cursors
+            .next(id, &scopes.owner(), &namespace, queries.page_size as usize)
+            .await?;
+
+        Self::page(docs, id, &queries.format, &scopes.redaction(database, collection))
+    }
+
+    fn page(
+        docs: Vec<Document>,
+        id: Option<String>,
+        format: &Option<Formats>,
+        redaction: &Redaction,
+    ) -> Result<Value> {
+        let mut results: Vec<Value> = Vec::new();
+        for mut doc in docs {
+            redaction.apply(&mut doc);
+            let bson = Self::extjson(&doc, format)?;
+            results.push(bson);
+        }
+
+        Ok(json!({ "docs": results, "cursor": id }))
+    }
+
+    fn extjson<T: Serialize>(value: &T, format: &Option<Formats>) -> Result<Value> {
+        Formats::json_only(format)?;
+        match format {
+            Some(Formats::Ejson) => Ok(to_bson(value)?.into_canonical_extjson()),
+            _ => Ok(to_bson(value)?.into_relaxed_extjson()),
+        }
+    }
+
+    // The single document matched by find_one, or by a find_one_and_* before or after it was modified
+    fn found(
+        doc: Option<Document>,
+        format: &Option<Formats>,
+        redaction: &Redaction,
+    ) -> Result<Value> {
+        match doc {
+            Some(mut doc) => {
+                log::debug!("Found a result");
+                redaction.apply(&mut doc);
+                let bson = Self::extjson(&doc, format)?;
+                Ok(bson)
+            }
+            None => {
+                log::debug!("No results found");
+                Ok(json!({ "msg": "no results found" }))
+            }
+        }
     }
 
     pub async fn insert_many(
@@ -241,6 +641,8 @@ impl DB {
         collection: &str,
         mut body: Vec<Document>,
         queries: Query<CustomInsertManyOptions>,
+        concern: &QueriesConcern,
+        transaction: Transaction,
     ) -> Result<Value> {
         if self.readonly {
             return Err(RestError::ReadOnly);
@@ -260,9 +662,16 @@ impl DB {
             }
         };
 
-        let options: InsertManyOptions = queries.0.into();
+        let mut options: InsertManyOptions = queries.0.into();
+        concerns!(options, concern, write);
+
+        let result = with_session!(
+            self.sessions.lock(&transaction).await?.as_mut(),
+            |session| collection.insert_many_with_session(body, options, session),
+            collection.insert_many(body, options)
+        );
 
-        match collection.insert_many(body, options).await {
+        match result {
             Ok(id) => {
                 log::debug!("Successfully inserted doc");
                 let response = json!({"Inserted": id.inserted_ids});
@@ -275,12 +684,448 @@ impl DB {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
+    pub async fn bulk_write(
+        &self,
+        database: &str,
+        collection: &str,
+        body: Vec<WriteModel>,
+        queries: Query<CustomInsertManyOptions>,
+        concern: &QueriesConcern,
+        transaction: Transaction,
+        scopes: &AuthorizeScope,
+    ) -> Result<Value> {
+        if self.readonly {
+            return Err(RestError::ReadOnly);
+        }
+
+        log::debug!("Running bulk write against {}.{}", database, collection);
+
+        // Checked up front, so a refused operation doesn't leave the ones before it applied
+        let redaction = scopes.redaction(database, collection);
+        for operation in &body {
+            match operation {
+                WriteModel::InsertOne { .. } => (),
+                WriteModel::UpdateOne { filter, update, .. }
+                | WriteModel::UpdateMany { filter, update, .. } => {
+                    redaction.filter(filter)?;
+                    redaction.update(update)?;
+                }
+                WriteModel::ReplaceOne {
+                    filter,
+                    replacement,
+                    ..
+                } => {
+                    redaction.filter(filter)?;
+                    redaction.replacement(replacement)?;
+                }
+                WriteModel::DeleteOne { filter, .. } | WriteModel::DeleteMany { filter, .. } => {
+                    redaction.filter(filter)?
+                }
+            };
+        }
+
+        let collection = self
+            .client
+            .database(database)
+            .collection::<Document>(collection);
+
+        let write_concern = concern.write_concern();
+        let bypass_document_validation = queries.0.bypass_document_validation;
+        let ordered = queries.0.ordered.unwrap_or(true);
+        let now = Utc::now();
+
+        // Every operation runs in the same session when inside a transaction
+        let mut session = self.sessions.lock(&transaction).await?;
+
+        let mut results: Vec<Value> = Vec::new();
+        let mut write_errors: Vec<Value> = Vec::new();
+
+        let mut inserted_ids = Document::new();
+        let mut upserted_ids = Document::new();
+        let mut matched_count = 0u64;
+        let mut modified_count = 0u64;
+        let mut deleted_count = 0u64;
+
+        for (index, operation) in body.into_iter().enumerate() {
+            // The one and many variants share their options and only differ in the driver call
+            let many = matches!(
+                operation,
+                WriteModel::UpdateMany { .. } | WriteModel::DeleteMany { .. }
+            );
+            let result = match operation {
+                WriteModel::InsertOne { mut document } => {
+                    if let Some(ref field) = queries.0.inject_time_field {
+                        document.insert(field, now);
+                    };
+                    let options = InsertOneOptions::builder()
+                        .bypass_document_validation(bypass_document_validation)
+                        .write_concern(write_concern.clone())
+                        .build();
+                    let result = with_session!(
+                        session.as_mut(),
+                        |session| collection.insert_one_with_session(document, options, session),
+                        collection.insert_one(document, options)
+                    );
+                    result.map(|r| {
+                        inserted_ids.insert(index.to_string(), r.inserted_id.clone());
+                        json!(r)
+                    })
+                }
+                WriteModel::UpdateOne {
+                    filter,
+                    update,
+                    upsert,
+                    array_filters,
+                    collation,
+                    hint,
+                }
+                | WriteModel::UpdateMany {
+                    filter,
+                    update,
+                    upsert,
+                    array_filters,
+                    collation,
+                    hint,
+                } => {
+                    let options = UpdateOptions::builder()
+                        .upsert(upsert)
+                        .array_filters(array_filters)
+                        .collation(collation)
+                        .hint(hint)
+                        .bypass_document_validation(bypass_document_validation)
+                        .write_concern(write_concern.clone())
+                        .build();
+                    let result = if many {
+                        with_session!(
+                            session.as_mut(),
+                            |session| collection.update_many_with_session(
+                                filter,
+                                update,
+                                options,
+                                session,
+                            ),
+                            collection.update_many(filter, update, options)
+                        )
+                    } else {
+                        with_session!(
+                            session.as_mut(),
+                            |session| collection.update_one_with_session(
+                                filter,
+                                update,
+                                options,
+                                session,
+                            ),
+                            collection.update_one(filter, update, options)
+                        )
+                    };
+                    result.map(|r| {
+                        matched_count += r.matched_count;
+                        modified_count += r.modified_count;
+                        if let Some(ref id) = r.upserted_id {
+                            upserted_ids.insert(index.to_string(), id.clone());
+                        };
+                        json!(r)
+                    })
+                }
+                WriteModel::ReplaceOne {
+                    filter,
+                    replacement,
+                    upsert,
+                    collation,
+                    hint,
+                } => {
+                    let options = ReplaceOptions::builder()
+                        .upsert(upsert)
+                        .collation(collation)
+                        .hint(hint)
+                        .bypass_document_validation(bypass_document_validation)
+                        .write_concern(write_concern.clone())
+                        .build();
+                    let result = with_session!(
+                        session.as_mut(),
+                        |session| collection.replace_one_with_session(
+                            filter,
+                            replacement,
+                            options,
+                            session,
+                        ),
+                        collection.replace_one(filter, replacement, options)
+                    );
+                    result.map(|r| {
+                        matched_count += r.matched_count;
+                        modified_count += r.modified_count;
+                        if let Some(ref id) = r.upserted_id {
+                            upserted_ids.insert(index.to_string(), id.clone());
+                        };
+                        json!(r)
+                    })
+                }
+                WriteModel::DeleteOne {
+                    filter,
+                    collation,
+                    hint,
+                }
+                | WriteModel::DeleteMany {
+                    filter,
+                    collation,
+                    hint,
+                } => {
+                    let options = DeleteOptions::builder()
+                        .collation(collation)
+                        .hint(hint)
+                        .write_concern(write_concern.clone())
+                        .build();
+                    let result = if many {
+                        with_session!(
+                            session.as_mut(),
+                            |session| collection.delete_many_with_session(filter, options, session),
+                            collection.delete_many(filter, options)
+                        )
+                    } else {
+                        with_session!(
+                            session.as_mut(),
+                            |session| collection.delete_one_with_session(filter, options, session),
+                            collection.delete_one(filter, options)
+                        )
+                    };
+                    result.map(|r| {
+                        deleted_count += r.deleted_count;
+                        json!(r)
+                    })
+                }
+            };
+
+            match result {
+                Ok(r) => results.push(r),
+                Err(e) => {
+                    log::error!("Error in bulk write operation {}: {}", index, e);
+                    results.push(Value::Null);
+                    write_errors.push(json!({"index": index, "error": e.to_string()}));
+
+                    // Ordered bulk writes stop at the first failed operation
+                    if ordered {
+                        break;
+                    }
+                }
+            }
+        }
+
+        log::debug!("Finished bulk write with {} errors", write_errors.len());
+
+        // Operations aren't rolled back, so everything before the failed one stays applied
+        let failed_index = write_errors.first().map(|error| error["index"].clone());
+
+        Ok(json!({
+            "insertedCount": inserted_ids.len(),
+            "matchedCount": matched_count,
+            "modifiedCount": modified_count,
+            "deletedCount": deleted_count,
+            "upsertedCount": upserted_ids.len(),
+            "insertedIds": to_bson(&inserted_ids)?.into_relaxed_extjson(),
+            "upsertedIds": to_bson(&upserted_ids)?.into_relaxed_extjson(),
+            "results": results,
+            "writeErrors": write_errors,
+            "failedIndex": failed_index,
+        }))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn import(
+        &self,
+        database: &str,
+        collection: &str,
+        mut body: BodyStream,
+        queries: Query<CustomInsertManyOptions>,
+        import: &QueriesImport,
+        input: Input,
+        concern: &QueriesConcern,
+        transaction: Transaction,
+    ) -> Result<Value> {
+        if self.readonly {
+            return Err(RestError::ReadOnly);
+        }
+
+        if input == Input::Bson {
+            return Err(RestError::BadRequest(
+                "_import takes newline delimited JSON or extended JSON".to_string(),
+            ));
+        }
+
+        log::debug!("Importing into {}.{}", database, collection);
+
+        let collection = self
+            .client
+            .database(database)
+            .collection::<Document>(collection);
+
+        let batch_size = import.batch_size.unwrap_or(1000).max(1);
+        let ordered = queries.0.ordered.unwrap_or(true);
+        let inject_time_field = queries.0.inject_time_field.clone();
+        let mut options: InsertManyOptions = queries.0.into();
+        concerns!(options, concern, write);
+
+        let mut session = self.sessions.lock(&transaction).await?;
+
+        let mut buffer: Vec<u8> = Vec::new();
+        // How much of the buffer is already known not to hold a newline
+        let mut scanned = 0usize;
+        let mut batch: Vec<Document> = Vec::new();
+        let mut batch_lines: Vec<usize> = Vec::new();
+        let mut errors: Vec<Value> = Vec::new();
+        let mut line = 0usize;
+        let mut inserted = 0usize;
+        let mut finished = false;
+
+        while !finished {
+            // Read in the next chunk, ending a last line that has no newline of its own
+            match body.next().await {
+                Some(chunk) => buffer.extend_from_slice(&chunk?),
+                None => {
+                    if !buffer.is_empty() {
+                        buffer.push(b'\n');
+                    };
+                    finished = true;
+                }
+            };
+
+            while let Some(position) = buffer[scanned..].iter().position(|b| b == &b'\n') {
+                let bytes: Vec<u8> = buffer.drain(..=scanned + position).collect();
+                scanned = 0;
+                line += 1;
+
+                let parsed = match std::str::from_utf8(&bytes).map(str::trim) {
+                    Ok("") => continue,
+                    Ok(text) => match input {
+                        Input::Ejson => body::ejson::<Document>(text.as_bytes()),
+                        _ => serde_json::from_str::<Document>(text).map_err(|e| e.to_string()),
+                    },
+                    Err(e) => Err(format!("Invalid UTF-8: {}", e)),
+                };
+
+                match parsed {
+                    Ok(mut doc) => {
+                        if let Some(ref field) = inject_time_field {
+                            doc.insert(field, Utc::now());
+                        };
+                        batch.push(doc);
+                        batch_lines.push(line);
+                    }
+                    Err(e) => {
+                        log::debug!("Error parsing line {}: {}", line, e);
+                        errors.push(json!({"line": line, "error": e.to_string()}));
+                        if ordered {
+                            finished = true;
+                            break;
+                        }
+                    }
+                };
+
+                if batch.len() >= batch_size {
+                    let docs = std::mem::take(&mut batch);
+                    let lines = std::mem::take(&mut batch_lines);
+                    let (count, failed) = Self::import_batch(
+                        &collection,
+                        docs,
+                        &lines,
+                        options.clone(),
+                        session.as_deref_mut(),
+                        &mut errors,
+                    )
+                    .await?;
+                    inserted += count;
+                    if failed && ordered {
+                        finished = true;
+                        break;
+                    }
+                }
+            }
+            scanned = buffer.len();
+
+            if !finished && buffer.len() > MAX_IMPORT_LINE {
+                return Err(RestError::BadRequest(format!(
+                    "Line {} is longer than {} bytes",
+                    line + 1,
+                    MAX_IMPORT_LINE
+                )));
+            }
+        }
+
+        // Flush whatever is left over, which only holds docs from before any ordered failure
+        if !batch.is_empty() {
+            let (count, _) = Self::import_batch(
+                &collection,
+                batch,
+                &batch_lines,
+                options,
+                session.as_deref_mut(),
+                &mut errors,
+            )
+            .await?;
+            inserted += count;
+        }
+
+        log::debug!("Imported {} docs with {} errors", inserted, errors.len());
+        Ok(json!({"Lines": line, "Inserted": inserted, "Errors": errors}))
+    }
+
+    // Insert a batch of imported docs, recording failures against their line numbers
+    async fn import_batch(
+        collection: &Collection<Document>,
+        batch: Vec<Document>,
+        lines: &[usize],
+        options: InsertManyOptions,
+        session: Option<&mut Session>,
+        errors: &mut Vec<Value>,
+    ) -> Result<(usize, bool)> {
+        let total = batch.len();
+        let ordered = options.ordered.unwrap_or(true);
+
+        let result = with_session!(
+            session,
+            |session| collection.insert_many_with_session(batch, options, session),
+            collection.insert_many(batch, options)
+        );
+
+        match result {
+            Ok(result) => {
+                log::debug!("Successfully inserted batch of {} docs", total);
+                Ok((result.inserted_ids.len(), false))
+            }
+            Err(e) => match *e.kind {
+                ErrorKind::BulkWrite(ref failure) => {
+                    let write_errors = failure.write_errors.clone().unwrap_or_default();
+                    for error in &write_errors {
+                        errors.push(json!({"line": lines.get(error.index), "error": error.message}));
+                    }
+                    if let Some(ref error) = failure.write_concern_error {
+                        errors.push(json!({"line": null, "error": error.message}));
+                    }
+
+                    // Ordered inserts stop at the first failure
+                    let inserted = if ordered {
+                        write_errors.iter().map(|e| e.index).min().unwrap_or(total)
+                    } else {
+                        total - write_errors.len()
+                    };
+                    Ok((inserted, true))
+                }
+                _ => {
+                    log::error!("Error inserting into mongo: {}", e);
+                    Err(e)?
+                }
+            },
+        }
+    }
+
     pub async fn insert_one(
         &self,
         database: &str,
         collection: &str,
         mut body: Document,
         queries: Query<CustomInsertOneOptions>,
+        concern: &QueriesConcern,
+        transaction: Transaction,
     ) -> Result<Value> {
         if self.readonly {
             return Err(RestError::ReadOnly);
@@ -298,9 +1143,16 @@ impl DB {
             body.insert(field, now);
         };
 
-        let options: InsertOneOptions = queries.0.into();
+        let mut options: InsertOneOptions = queries.0.into();
+        concerns!(options, concern, write);
 
-        match collection.insert_one(body, options).await {
+        let result = with_session!(
+            self.sessions.lock(&transaction).await?.as_mut(),
+            |session| collection.insert_one_with_session(body, options, session),
+            collection.insert_one(body, options)
+        );
+
+        match result {
             Ok(id) => {
                 log::debug!("Successfully inserted doc");
                 let response = json!({"Inserted": id.inserted_id});
@@ -318,6 +1170,9 @@ impl DB {
         database: &str,
         collection: &str,
         payload: DeleteOne,
+        concern: &QueriesConcern,
+        transaction: Transaction,
+        scopes: &AuthorizeScope,
     ) -> Result<Value> {
         if self.readonly {
             return Err(RestError::ReadOnly);
@@ -325,15 +1180,23 @@ impl DB {
 
         log::debug!("Deleting many from {}.{}", database, collection);
 
+        scopes.redaction(database, collection).filter(&payload.filter)?;
+
         let collection = self
             .client
             .database(database)
             .collection::<Document>(collection);
 
-        match collection
-            .delete_many(payload.filter, payload.options)
-            .await
-        {
+        let mut options = payload.options.unwrap_or_default();
+        concerns!(options, concern, write);
+
+        let result = with_session!(
+            self.sessions.lock(&transaction).await?.as_mut(),
+            |session| collection.delete_many_with_session(payload.filter, options, session),
+            collection.delete_many(payload.filter, options)
+        );
+
+        match result {
             Ok(result) => {
                 log::debug!("Successfully deleted docs");
                 let response = json!({"Deleted": result.deleted_count});
@@ -351,6 +1214,9 @@ impl DB {
         database: &str,
         collection: &str,
         payload: DeleteOne,
+        concern: &QueriesConcern,
+        transaction: Transaction,
+        scopes: &AuthorizeScope,
     ) -> Result<Value> {
         if self.readonly {
             return Err(RestError::ReadOnly);
@@ -358,12 +1224,23 @@ impl DB {
 
         log::debug!("Deleting one from {}.{}", database, collection);
 
+        scopes.redaction(database, collection).filter(&payload.filter)?;
+
         let collection = self
             .client
             .database(database)
             .collection::<Document>(collection);
 
-        match collection.delete_one(payload.filter, payload.options).await {
+        let mut options = payload.options.unwrap_or_default();
+        concerns!(options, concern, write);
+
+        let result = with_session!(
+            self.sessions.lock(&transaction).await?.as_mut(),
+            |session| collection.delete_one_with_session(payload.filter, options, session),
+            collection.delete_one(payload.filter, options)
+        );
+
+        match result {
             Ok(result) => {
                 log::debug!("Successfully deleted doc");
                 let response = json!({"Deleted": result.deleted_count});
@@ -381,6 +1258,9 @@ impl DB {
         database: &str,
         collection: &str,
         payload: Update,
+        concern: &QueriesConcern,
+        transaction: Transaction,
+        scopes: &AuthorizeScope,
     ) -> Result<Value> {
         if self.readonly {
             return Err(RestError::ReadOnly);
@@ -388,15 +1268,30 @@ impl DB {
 
         log::debug!("Updating one from {}.{}", database, collection);
 
+        let redaction = scopes.redaction(database, collection);
+        redaction.filter(&payload.filter)?;
+        redaction.update(&payload.update)?;
+
         let collection = self
             .client
             .database(database)
             .collection::<Document>(collection);
 
-        match collection
-            .update_one(payload.filter, payload.update, payload.options)
-            .await
-        {
+        let mut options = payload.options.unwrap_or_default();
+        concerns!(options, concern, write);
+
+        let result = with_session!(
+            self.sessions.lock(&transaction).await?.as_mut(),
+            |session| collection.update_one_with_session(
+                payload.filter,
+                payload.update,
+                options,
+                session,
+            ),
+            collection.update_one(payload.filter, payload.update, options)
+        );
+
+        match result {
             Ok(result) => {
                 log::debug!("Successfully updated doc");
                 let response = json!(result);
@@ -414,6 +1309,9 @@ impl DB {
         database: &str,
         collection: &str,
         payload: Update,
+        concern: &QueriesConcern,
+        transaction: Transaction,
+        scopes: &AuthorizeScope,
     ) -> Result<Value> {
         if self.readonly {
             return Err(RestError::ReadOnly);
@@ -421,15 +1319,30 @@ impl DB {
 
         log::debug!("Updating many from {}.{}", database, collection);
 
+        let redaction = scopes.redaction(database, collection);
+        redaction.filter(&payload.filter)?;
+        redaction.update(&payload.update)?;
+
         let collection = self
             .client
             .database(database)
             .collection::<Document>(collection);
 
-        match collection
-            .update_many(payload.filter, payload.update, payload.options)
-            .await
-        {
+        let mut options = payload.options.unwrap_or_default();
+        concerns!(options, concern, write);
+
+        let result = with_session!(
+            self.sessions.lock(&transaction).await?.as_mut(),
+            |session| collection.update_many_with_session(
+                payload.filter,
+                payload.update,
+                options,
+                session,
+            ),
+            collection.update_many(payload.filter, payload.update, options)
+        );
+
+        match result {
             Ok(result) => {
                 log::debug!("Successfully updated docs");
                 let response = json!(result);
@@ -442,12 +1355,225 @@ impl DB {
         }
     }
 
+    pub async fn replace_one(
+        &self,
+        database: &str,
+        collection: &str,
+        payload: Replace,
+        concern: &QueriesConcern,
+        transaction: Transaction,
+        scopes: &AuthorizeScope,
+    ) -> Result<Value> {
+        if self.readonly {
+            return Err(RestError::ReadOnly);
+        }
+
+        log::debug!("Replacing one in {}.{}", database, collection);
+
+        let redaction = scopes.redaction(database, collection);
+        redaction.filter(&payload.filter)?;
+        redaction.replacement(&payload.replacement)?;
+
+        let collection = self
+            .client
+            .database(database)
+            .collection::<Document>(collection);
+
+        // A top level upsert overrides the one set in options
+        let mut options = payload.options.unwrap_or_default();
+        if payload.upsert.is_some() {
+            options.upsert = payload.upsert;
+        };
+        concerns!(options, concern, write);
+
+        let result = with_session!(
+            self.sessions.lock(&transaction).await?.as_mut(),
+            |session| collection.replace_one_with_session(
+                payload.filter,
+                payload.replacement,
+                options,
+                session,
+            ),
+            collection.replace_one(payload.filter, payload.replacement, options)
+        );
+
+        match result {
+            Ok(result) => {
+                log::debug!("Successfully replaced doc");
+                let response = json!(result);
+                Ok(response)
+            }
+            Err(e) => {
+                log::error!("Error replacing in mongo: {}", e);
+                Err(e)?
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn find_one_and_update(
+        &self,
+        database: &str,
+        collection: &str,
+        payload: FindOneAndUpdate,
+        queries: &QueriesFormat,
+        concern: &QueriesConcern,
+        transaction: Transaction,
+        scopes: &AuthorizeScope,
+    ) -> Result<Value> {
+        if self.readonly {
+            return Err(RestError::ReadOnly);
+        }
+
+        log::debug!("Finding and updating one in {}.{}", database, collection);
+
+        // Checked before the write, which the response would otherwise be lost after
+        Formats::json_only(&queries.format)?;
+
+        let redaction = scopes.redaction(database, collection);
+        redaction.update(&payload.update)?;
+
+        let collection = self
+            .client
+            .database(database)
+            .collection::<Document>(collection);
+
+        let mut options = payload.options.unwrap_or_default();
+        concerns!(options, concern, write);
+        options.projection = redaction.projection(options.projection)?;
+        redaction.filter(&payload.filter)?;
+        redaction.sort(&options.sort)?;
+
+        let result = with_session!(
+            self.sessions.lock(&transaction).await?.as_mut(),
+            |session| collection.find_one_and_update_with_session(
+                payload.filter,
+                payload.update,
+                options,
+                session,
+            ),
+            collection.find_one_and_update(payload.filter, payload.update, options)
+        );
+
+        match result {
+            Ok(doc) => Self::found(doc, &queries.format, &redaction),
+            Err(e) => {
+                log::error!("Error updating in mongo: {}", e);
+                Err(e)?
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn find_one_and_replace(
+        &self,
+        database: &str,
+        collection: &str,
+        payload: FindOneAndReplace,
+        queries: &QueriesFormat,
+        concern: &QueriesConcern,
+        transaction: Transaction,
+        scopes: &AuthorizeScope,
+    ) -> Result<Value> {
+        if self.readonly {
+            return Err(RestError::ReadOnly);
+        }
+
+        log::debug!("Finding and replacing one in {}.{}", database, collection);
+
+        // Checked before the write, which the response would otherwise be lost after
+        Formats::json_only(&queries.format)?;
+
+        let redaction = scopes.redaction(database, collection);
+        redaction.replacement(&payload.replacement)?;
+
+        let collection = self
+            .client
+            .database(database)
+            .collection::<Document>(collection);
+
+        let mut options = payload.options.unwrap_or_default();
+        concerns!(options, concern, write);
+        options.projection = redaction.projection(options.projection)?;
+        redaction.filter(&payload.filter)?;
+        redaction.sort(&options.sort)?;
+
+        let result = with_session!(
+            self.sessions.lock(&transaction).await?.as_mut(),
+            |session| collection.find_one_and_replace_with_session(
+                payload.filter,
+                payload.replacement,
+                options,
+                session,
+            ),
+            collection.find_one_and_replace(payload.filter, payload.replacement, options)
+        );
+
+        match result {
+            Ok(doc) => Self::found(doc, &queries.format, &redaction),
+            Err(e) => {
+                log::error!("Error replacing in mongo: {}", e);
+                Err(e)?
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn find_one_and_delete(
+        &self,
+        database: &str,
+        collection: &str,
+        payload: FindOneAndDelete,
+        queries: &QueriesFormat,
+        concern: &QueriesConcern,
+        transaction: Transaction,
+        scopes: &AuthorizeScope,
+    ) -> Result<Value> {
+        if self.readonly {
+            return Err(RestError::ReadOnly);
+        }
+
+        log::debug!("Finding and deleting one in {}.{}", database, collection);
+
+        // Checked before the write, which the response would otherwise be lost after
+        Formats::json_only(&queries.format)?;
+
+        let redaction = scopes.redaction(database, collection);
+
+        let collection = self
+            .client
+            .database(database)
+            .collection::<Document>(collection);
+
+        let mut options = payload.options.unwrap_or_default();
+        concerns!(options, concern, write);
+        options.projection = redaction.projection(options.projection)?;
+        redaction.filter(&payload.filter)?;
+        redaction.sort(&options.sort)?;
+
+        let result = with_session!(
+            self.sessions.lock(&transaction).await?.as_mut(),
+            |session| collection.find_one_and_delete_with_session(payload.filter, options, session),
+            collection.find_one_and_delete(payload.filter, options)
+        );
+
+        match result {
+            Ok(doc) => Self::found(doc, &queries.format, &redaction),
+            Err(e) => {
+                log::error!("Error deleting from mongo: {}", e);
+                Err(e)?
+            }
+        }
+    }
+
     pub async fn distinct(
         &self,
         database: &str,
         collection: &str,
         payload: Distinct,
         queries: &QueriesFormat,
+        concern: &QueriesConcern,
+        scopes: &AuthorizeScope,
     ) -> Result<Value> {
         log::debug!(
             "Searching for distinct values in {}.{}",
@@ -455,21 +1581,28 @@ impl DB {
             collection
         );
 
+        // Distinct values would list a redacted field outright
+        let redaction = scopes.redaction(database, collection);
+        redaction.field(&payload.field_name)?;
+        if let Some(ref filter) = payload.filter {
+            redaction.filter(filter)?;
+        };
+
         let collection = self
             .client
             .database(database)
             .collection::<Document>(collection);
 
+        let mut options = payload.options.unwrap_or_default();
+        concerns!(options, concern, read);
+
         match collection
-            .distinct(payload.field_name, payload.filter, payload.options)
+            .distinct(payload.field_name, payload.filter, options)
             .await
         {
             Ok(doc) => {
                 log::debug!("Found a result");
-                let bson = match &queries.format {
-                    None | Some(Formats::Json) => to_bson(&doc)?.into_relaxed_extjson(),
-                    Some(Formats::Ejson) => to_bson(&doc)?.into_canonical_extjson(),
-                };
+                let bson = Self::extjson(&doc, &queries.format)?;
                 Ok(bson)
             }
             Err(e) => {
@@ -484,32 +1617,207 @@ impl DB {
         collection: &str,
         payload: FindOne,
         queries: &QueriesFormat,
+        concern: &QueriesConcern,
+        scopes: &AuthorizeScope,
     ) -> Result<Value> {
         log::debug!("Searching {}.{}", database, collection);
 
+        let redaction = scopes.redaction(database, collection);
+
         let collection = self
             .client
             .database(database)
             .collection::<Document>(collection);
 
-        match collection.find_one(payload.filter, payload.options).await {
+        let mut options = payload.options.unwrap_or_default();
+        concerns!(options, concern, read);
+        options.projection = redaction.projection(options.projection)?;
+        redaction.filter(&payload.filter)?;
+        redaction.sort(&options.sort)?;
+        redaction.sort(&options.min)?;
+        redaction.sort(&options.max)?;
+
+        match collection.find_one(payload.filter, options).await {
+            Ok(doc) => Self::found(doc, &queries.format, &redaction),
+            Err(e) => {
+                log::error!("Error searching mongodb: {}", e);
+                return Err(e)?;
+            }
+        }
+    }
+
+    pub async fn document_get(
+        &self,
+        database: &str,
+        collection: &str,
+        id: DocumentId,
+        queries: &QueriesFormat,
+        scopes: &AuthorizeScope,
+    ) -> Result<Value> {
+        log::debug!("Getting document {} in {}.{}", id.0, database, collection);
+
+        let redaction = scopes.redaction(database, collection);
+
+        let collection = self
+            .client
+            .database(database)
+            .collection::<Document>(collection);
+
+        match collection.find_one(doc! {"_id": id.0}, None).await {
             Ok(result) => match result {
-                Some(doc) => {
+                Some(mut doc) => {
                     log::debug!("Found a result");
-                    let bson = match &queries.format {
-                        None | Some(Formats::Json) => to_bson(&doc)?.into_relaxed_extjson(),
-                        Some(Formats::Ejson) => to_bson(&doc)?.into_canonical_extjson(),
-                    };
+                    redaction.apply(&mut doc);
+                    let bson = Self::extjson(&doc, &queries.format)?;
                     Ok(bson)
                 }
                 None => {
                     log::debug!("No results found");
-                    Ok(json!({ "msg": "no results found" }))
+                    Err(RestError::NotFound)
                 }
             },
             Err(e) => {
                 log::error!("Error searching mongodb: {}", e);
-                return Err(e)?;
+                Err(e)?
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn document_replace(
+        &self,
+        database: &str,
+        collection: &str,
+        id: DocumentId,
+        mut body: Document,
+        queries: &QueriesUpsert,
+        transaction: Transaction,
+        scopes: &AuthorizeScope,
+    ) -> Result<Value> {
+        if self.readonly {
+            return Err(RestError::ReadOnly);
+        }
+
+        log::debug!("Replacing document {} in {}.{}", id.0, database, collection);
+
+        scopes.redaction(database, collection).replacement(&body)?;
+
+        let collection = self
+            .client
+            .database(database)
+            .collection::<Document>(collection);
+
+        // The path always decides which document is written
+        body.insert("_id", id.0.clone());
+        let filter = doc! {"_id": id.0};
+        let options = ReplaceOptions::builder().upsert(queries.upsert).build();
+
+        let result = with_session!(
+            self.sessions.lock(&transaction).await?.as_mut(),
+            |session| collection.replace_one_with_session(filter, body, options, session),
+            collection.replace_one(filter, body, options)
+        );
+
+        match result {
+            Ok(result) if result.matched_count == 0 && result.upserted_id.is_none() => {
+                log::debug!("No results found");
+                Err(RestError::NotFound)
+            }
+            Ok(result) => {
+                log::debug!("Successfully replaced doc");
+                Ok(json!(result))
+            }
+            Err(e) => {
+                log::error!("Error replacing in mongo: {}", e);
+                Err(e)?
+            }
+        }
+    }
+
+    pub async fn document_update(
+        &self,
+        database: &str,
+        collection: &str,
+        id: DocumentId,
+        body: Document,
+        transaction: Transaction,
+        scopes: &AuthorizeScope,
+    ) -> Result<Value> {
+        if self.readonly {
+            return Err(RestError::ReadOnly);
+        }
+
+        log::debug!("Updating document {} in {}.{}", id.0, database, collection);
+
+        scopes.redaction(database, collection).modifiers(&body)?;
+
+        let collection = self
+            .client
+            .database(database)
+            .collection::<Document>(collection);
+
+        let filter = doc! {"_id": id.0};
+
+        let result = with_session!(
+            self.sessions.lock(&transaction).await?.as_mut(),
+            |session| collection.update_one_with_session(filter, body, None, session),
+            collection.update_one(filter, body, None)
+        );
+
+        match result {
+            Ok(result) if result.matched_count == 0 => {
+                log::debug!("No results found");
+                Err(RestError::NotFound)
+            }
+            Ok(result) => {
+                log::debug!("Successfully updated doc");
+                Ok(json!(result))
+            }
+            Err(e) => {
+                log::error!("Error updating in mongo: {}", e);
+                Err(e)?
+            }
+        }
+    }
+
+    pub async fn document_delete(
+        &self,
+        database: &str,
+        collection: &str,
+        id: DocumentId,
+        transaction: Transaction,
+    ) -> Result<Value> {
+        if self.readonly {
+            return Err(RestError::ReadOnly);
+        }
+
+        log::debug!("Deleting document {} in {}.{}", id.0, database, collection);
+
+        let collection = self
+            .client
+            .database(database)
+            .collection::<Document>(collection);
+
+        let filter = doc! {"_id": id.0};
+
+        let result = with_session!(
+            self.sessions.lock(&transaction).await?.as_mut(),
+            |session| collection.delete_one_with_session(filter, None, session),
+            collection.delete_one(filter, None)
+        );
+
+        match result {
+            Ok(result) if result.deleted_count == 0 => {
+                log::debug!("No results found");
+                Err(RestError::NotFound)
+            }
+            Ok(result) => {
+                log::debug!("Successfully deleted doc");
+                Ok(json!({"Deleted": result.deleted_count}))
+            }
+            Err(e) => {
+                log::error!("Error deleting from mongo: {}", e);
+                Err(e)?
             }
         }
     }
@@ -531,15 +1839,30 @@ impl DB {
         }
     }
 
-    pub async fn count(&self, database: &str, collection: &str, payload: Count) -> Result<Value> {
+    pub async fn count(
+        &self,
+        database: &str,
+        collection: &str,
+        payload: Count,
+        concern: &QueriesConcern,
+        scopes: &AuthorizeScope,
+    ) -> Result<Value> {
         log::debug!("Getting document count in {}", database);
 
+        scopes.redaction(database, collection).filter(&payload.filter)?;
+
         let collection = self
             .client
             .database(&database)
             .collection::<Document>(collection);
 
-        match collection.count_documents(payload.filter, payload.options).await {
+        let mut options = payload.options.unwrap_or_default();
+        concerns!(options, concern, read);
+
+        match collection
+            .count_documents(payload.filter, options)
+            .await
+        {
             Ok(count) => {
                 log::debug!("Successfully counted docs with filter in {}", database);
                 let result = json!({ "docs": count });
@@ -573,6 +1896,39 @@ impl DB {
         }
     }
 
+    // Samples documents to report the fields they hold, and their types
+    pub async fn schema(
+        &self,
+        database: &str,
+        collection: &str,
+        queries: &QueriesSchema,
+        scopes: &AuthorizeScope,
+    ) -> Result<Value> {
+        log::debug!("Sampling schema of {}.{}", database, collection);
+
+        // The report carries example values, so it is built from redacted documents
+        let redaction = scopes.redaction(database, collection);
+
+        let collection = self
+            .client
+            .database(database)
+            .collection::<Document>(collection);
+
+        let sample_size = queries.sample_size.unwrap_or(1000).max(1);
+        let mut cursor = collection
+            .aggregate(vec![doc! {"$sample": {"size": sample_size}}], None)
+            .await?;
+
+        let mut schema = Schema::default();
+        while let Some(next) = cursor.next().await {
+            let mut doc = next?;
+            redaction.apply(&mut doc);
+            schema.add(&doc);
+        }
+
+        Ok(schema.report(queries.json_schema.unwrap_or(false)))
+    }
+
     pub async fn coll_indexes(
         &self,
         database: &str,
@@ -592,10 +1948,7 @@ impl DB {
         while let Some(next) = cursor.next().await {
             match next {
                 Ok(doc) => {
-                    let bson = match &queries.format {
-                        None | Some(Formats::Json) => to_bson(&doc)?.into_relaxed_extjson(),
-                        Some(Formats::Ejson) => to_bson(&doc)?.into_canonical_extjson(),
-                    };
+                    let bson = Self::extjson(&doc, &queries.format)?;
                     result.push(bson);
                 }
                 Err(e) => {
@@ -640,7 +1993,8 @@ impl DB {
         &self,
         database: &str,
         collection: &str,
-    ) -> Result<StreamBody<impl Stream<Item = Result<Bytes>>>> {
+        framing: Framing,
+    ) -> Result<impl IntoResponse> {
         log::debug!("Getting index stats");
 
         let mut commands = Vec::new();
@@ -653,9 +2007,10 @@ impl DB {
         };
 
         let queries = QueriesFormat::default();
+        let concern = QueriesConcern::default();
 
         match self
-            .aggregate(database, collection, payload, axum::extract::Query(queries))
+            .aggregate(database, collection, payload, axum::extract::Query(queries), framing, &concern, &AuthorizeScope::default())
             .await
         {
             Ok(output) => {
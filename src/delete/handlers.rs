@@ -1,23 +1,31 @@
-use axum::{extract::Path, Extension, Json};
+use axum::{
+    extract::{Path, Query},
+    Extension, Json,
+};
 use serde_json::{json, Value};
 
-use crate::delete::structs::DeleteOne;
+use crate::body::Payload;
+use crate::delete::structs::{DeleteOne, FindOneAndDelete};
 use crate::error::Error as RestError;
+use crate::queries::{QueriesConcern, QueriesFormat};
 use crate::scopes::AuthorizeScope;
+use crate::sessions::Transaction;
 use crate::State;
 
 pub async fn delete_many(
     Extension(state): Extension<State>,
     Extension(scopes): Extension<AuthorizeScope>,
     Path((db, coll)): Path<(String, String)>,
-    Json(payload): Json<DeleteOne>,
+    concern: Query<QueriesConcern>,
+    transaction: Transaction,
+    Payload(payload): Payload<DeleteOne>,
 ) -> Result<Json<Value>, RestError> {
     // Validate that the client has access to this database
-    scopes.write(&db)?;
+    scopes.write(&db, Some(&coll))?;
 
     log::info!("{{\"fn\": \"delete_many\", \"db\": \"{}\", \"coll\": \"{}\"}}", &db, &coll);
     Ok(Json(json!(
-        state.db.delete_many(&db, &coll, payload).await?
+        state.db.delete_many(&db, &coll, payload, &concern, transaction, &scopes).await?
     )))
 }
 
@@ -25,11 +33,33 @@ pub async fn delete_one(
     Extension(state): Extension<State>,
     Extension(scopes): Extension<AuthorizeScope>,
     Path((db, coll)): Path<(String, String)>,
-    Json(payload): Json<DeleteOne>,
+    concern: Query<QueriesConcern>,
+    transaction: Transaction,
+    Payload(payload): Payload<DeleteOne>,
 ) -> Result<Json<Value>, RestError> {
     // Validate that the client has access to this database
-    scopes.write(&db)?;
+    scopes.write(&db, Some(&coll))?;
 
     log::info!("{{\"fn\": \"delete_one\", \"db\": \"{}\", \"coll\": \"{}\"}}", &db, &coll);
-    Ok(Json(json!(state.db.delete_one(&db, &coll, payload).await?)))
+    Ok(Json(json!(
+        state.db.delete_one(&db, &coll, payload, &concern, transaction, &scopes).await?
+    )))
+}
+
+pub async fn find_one_and_delete(
+    Extension(state): Extension<State>,
+    Extension(scopes): Extension<AuthorizeScope>,
+    Path((db, coll)): Path<(String, String)>,
+    transaction: Transaction,
+    queries: Query<QueriesFormat>,
+    concern: Query<QueriesConcern>,
+    Payload(payload): Payload<FindOneAndDelete>,
+) -> Result<Json<Value>, RestError> {
+    // Validate that the client has access to this database
+    scopes.write(&db, Some(&coll))?;
+
+    log::info!("{{\"fn\": \"find_one_and_delete\", \"db\": \"{}\", \"coll\": \"{}\"}}", &db, &coll);
+    Ok(Json(json!(
+        state.db.find_one_and_delete(&db, &coll, payload, &queries, &concern, transaction, &scopes).await?
+    )))
 }
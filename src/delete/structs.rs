@@ -1,5 +1,5 @@
 use bson::Document;
-use mongodb::options::DeleteOptions;
+use mongodb::options::{DeleteOptions, FindOneAndDeleteOptions};
 use serde::Deserialize;
 
 #[derive(Deserialize, Debug, Clone)]
@@ -7,3 +7,9 @@ pub struct DeleteOne {
     pub filter: Document,
     pub options: Option<DeleteOptions>,
 }
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct FindOneAndDelete {
+    pub filter: Document,
+    pub options: Option<FindOneAndDeleteOptions>,
+}
@@ -0,0 +1,85 @@
+use axum::{
+    extract::{Path, Query},
+    Extension, Json,
+};
+use bson::Document;
+use serde_json::{json, Value};
+
+use crate::body::Payload;
+use crate::document::structs::{DocumentId, QueriesId, QueriesUpsert};
+use crate::error::Error as RestError;
+use crate::queries::QueriesFormat;
+use crate::scopes::AuthorizeScope;
+use crate::sessions::Transaction;
+use crate::State;
+
+pub async fn document_get(
+    Extension(state): Extension<State>,
+    Extension(scopes): Extension<AuthorizeScope>,
+    Path((db, coll, id)): Path<(String, String, String)>,
+    id_type: Query<QueriesId>,
+    queries: Query<QueriesFormat>,
+) -> Result<Json<Value>, RestError> {
+    // Validate that the client has access to this database
+    scopes.read(&db, Some(&coll))?;
+
+    log::info!("{{\"fn\": \"document_get\", \"db\": \"{}\", \"coll\": \"{}\"}}", &db, &coll);
+    let id = DocumentId::parse(id, &id_type.id_type)?;
+    Ok(Json(json!(
+        state.db.document_get(&db, &coll, id, &queries, &scopes).await?
+    )))
+}
+
+pub async fn document_replace(
+    Extension(state): Extension<State>,
+    Extension(scopes): Extension<AuthorizeScope>,
+    Path((db, coll, id)): Path<(String, String, String)>,
+    id_type: Query<QueriesId>,
+    transaction: Transaction,
+    queries: Query<QueriesUpsert>,
+    Payload(body): Payload<Document>,
+) -> Result<Json<Value>, RestError> {
+    // Validate that the client has access to this database
+    scopes.write(&db, Some(&coll))?;
+
+    log::info!("{{\"fn\": \"document_replace\", \"db\": \"{}\", \"coll\": \"{}\"}}", &db, &coll);
+    let id = DocumentId::parse(id, &id_type.id_type)?;
+    Ok(Json(json!(
+        state.db.document_replace(&db, &coll, id, body, &queries, transaction, &scopes).await?
+    )))
+}
+
+pub async fn document_update(
+    Extension(state): Extension<State>,
+    Extension(scopes): Extension<AuthorizeScope>,
+    Path((db, coll, id)): Path<(String, String, String)>,
+    id_type: Query<QueriesId>,
+    transaction: Transaction,
+    Payload(body): Payload<Document>,
+) -> Result<Json<Value>, RestError> {
+    // Validate that the client has access to this database
+    scopes.write(&db, Some(&coll))?;
+
+    log::info!("{{\"fn\": \"document_update\", \"db\": \"{}\", \"coll\": \"{}\"}}", &db, &coll);
+    let id = DocumentId::parse(id, &id_type.id_type)?;
+    Ok(Json(json!(
+        state.db.document_update(&db, &coll, id, body, transaction, &scopes).await?
+    )))
+}
+
+pub async fn document_delete(
+    Extension(state): Extension<State>,
+    Extension(scopes): Extension<AuthorizeScope>,
+    Path((db, coll, id)): Path<(String, String, String)>,
+    id_type: Query<QueriesId>,
+    transaction: Transaction,
+) -> Result<Json<Value>, RestError> {
+    // Validate that the client has access to this database
+    scopes.write(&db, Some(&coll))?;
+
+    log::info!("{{\"fn\": \"document_delete\", \"db\": \"{}\", \"coll\": \"{}\"}}", &db, &coll);
+    let id = DocumentId::parse(id, &id_type.id_type)?;
+    Ok(Json(json!(
+        state.db.document_delete(&db, &coll, id, transaction).await?
+    )))
+}
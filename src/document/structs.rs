@@ -0,0 +1,70 @@
+use bson::{oid::ObjectId, Bson, Uuid};
+use serde::Deserialize;
+
+use crate::error::Error as RestError;
+
+#[derive(Debug, Clone)]
+pub struct DocumentId(pub Bson);
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct QueriesUpsert {
+    pub upsert: Option<bool>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub enum IdType {
+    #[serde(rename = "string")]
+    String,
+    #[serde(rename = "int")]
+    Int,
+    #[serde(rename = "oid")]
+    Oid,
+    #[serde(rename = "uuid")]
+    Uuid,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct QueriesId {
+    pub id_type: Option<IdType>,
+}
+
+impl DocumentId {
+    // Parse the _id as the type asked for, or detect it when no id_type is given
+    pub fn parse(item: String, id_type: &Option<IdType>) -> Result<Self, RestError> {
+        let invalid = |e: &dyn std::fmt::Display| {
+            RestError::BadRequest(format!("Invalid id {}: {}", item, e))
+        };
+
+        match id_type {
+            None => Ok(item.into()),
+            Some(IdType::String) => Ok(DocumentId(Bson::String(item))),
+            Some(IdType::Int) => match item.parse::<i64>() {
+                Ok(int) => Ok(DocumentId(Bson::Int64(int))),
+                Err(e) => Err(invalid(&e)),
+            },
+            Some(IdType::Oid) => match ObjectId::parse_str(&item) {
+                Ok(oid) => Ok(DocumentId(Bson::ObjectId(oid))),
+                Err(e) => Err(invalid(&e)),
+            },
+            Some(IdType::Uuid) => match Uuid::parse_str(&item) {
+                Ok(uuid) => Ok(DocumentId(uuid.into())),
+                Err(e) => Err(invalid(&e)),
+            },
+        }
+    }
+}
+
+impl From<String> for DocumentId {
+    // Detect the _id type from the path: ObjectId, then integer, then UUID, falling back to string
+    fn from(item: String) -> Self {
+        if let Ok(oid) = ObjectId::parse_str(&item) {
+            DocumentId(Bson::ObjectId(oid))
+        } else if let Ok(int) = item.parse::<i64>() {
+            DocumentId(Bson::Int64(int))
+        } else if let Ok(uuid) = Uuid::parse_str(&item) {
+            DocumentId(uuid.into())
+        } else {
+            DocumentId(Bson::String(item))
+        }
+    }
+}
@@ -4,6 +4,7 @@ use axum::{
     http::StatusCode,
     response::{IntoResponse, Response},
 };
+use serde_json::json;
 use std::fmt;
 
 #[derive(Debug)]
@@ -14,6 +15,12 @@ pub enum Error {
     ReadOnly,
     JwtDecode,
     UnauthorizedClient,
+    TransactionNotFound,
+    CursorNotFound,
+    NotFound,
+    BadRequest(String),
+    Discovery(String),
+    Config(String),
     Mongo(mongodb::error::Error),
     Bson(bson::document::ValueAccessError),
     DeError(bson::de::Error),
@@ -22,7 +29,10 @@ pub enum Error {
     InvalidUri(hyper::http::uri::InvalidUri),
     SerdeJson(serde_json::Error),
     Hyper(hyper::Error),
+    Axum(axum::Error),
     Jwt(jsonwebtoken::errors::Error),
+    Arrow(arrow::error::ArrowError),
+    Parquet(parquet::errors::ParquetError),
 }
 
 impl std::error::Error for Error {}
@@ -56,9 +66,26 @@ impl fmt::Display for Error {
             Error::InvalidUri(ref err) => write!(f, "{{\"error\": \"{}\"}}", err),
             Error::SerdeJson(ref err) => write!(f, "{{\"error\": \"{}\"}}", err),
             Error::Hyper(ref err) => write!(f, "{{\"error\": \"{}\"}}", err),
+            Error::Axum(ref err) => write!(f, "{}", json!({ "error": err.to_string() })),
             Error::UnauthorizedClient => f.write_str("{\"error\": \"Unauthorized\"}"),
             Error::JwtDecode => f.write_str("{\"error\": \"Unable to decode JWT\"}"),
+            Error::TransactionNotFound => f.write_str("{\"error\": \"Transaction not found\"}"),
+            Error::NotFound => f.write_str("{\"error\": \"Document not found\"}"),
+            Error::CursorNotFound => f.write_str("{\"error\": \"Cursor not found\"}"),
+            Error::BadRequest(ref err) => write!(f, "{}", json!({ "error": err })),
             Error::Jwt(ref err) => write!(f, "{{\"error\": \"{}\"}}", err),
+            Error::Discovery(ref err) => write!(
+                f,
+                "{}",
+                json!({ "error": format!("OIDC discovery failed: {}", err) })
+            ),
+            Error::Config(ref err) => write!(
+                f,
+                "{}",
+                json!({ "error": format!("Invalid configuration: {}", err) })
+            ),
+            Error::Arrow(ref err) => write!(f, "{}", json!({ "error": err.to_string() })),
+            Error::Parquet(ref err) => write!(f, "{}", json!({ "error": err.to_string() })),
         }
     }
 }
@@ -70,6 +97,10 @@ impl IntoResponse for Error {
 
         let status_code = match self {
             Error::ReadOnly | Error::UnauthorizedClient => StatusCode::FORBIDDEN,
+            Error::TransactionNotFound | Error::NotFound | Error::CursorNotFound => {
+                StatusCode::NOT_FOUND
+            }
+            Error::BadRequest(_) => StatusCode::BAD_REQUEST,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         };
 
@@ -125,8 +156,26 @@ impl From<hyper::Error> for Error {
     }
 }
 
+impl From<axum::Error> for Error {
+    fn from(err: axum::Error) -> Error {
+        Error::Axum(err)
+    }
+}
+
 impl From<jsonwebtoken::errors::Error> for Error {
     fn from(err: jsonwebtoken::errors::Error) -> Error {
         Error::Jwt(err)
     }
 }
+
+impl From<arrow::error::ArrowError> for Error {
+    fn from(err: arrow::error::ArrowError) -> Error {
+        Error::Arrow(err)
+    }
+}
+
+impl From<parquet::errors::ParquetError> for Error {
+    fn from(err: parquet::errors::ParquetError) -> Error {
+        Error::Parquet(err)
+    }
+}
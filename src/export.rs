@@ -0,0 +1,537 @@
+use arrow::array::{
+    ArrayRef, BooleanArray, Float64Array, Int32Array, Int64Array, StringArray,
+    TimestampMillisecondArray,
+};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use arrow::ipc::writer::StreamWriter;
+use arrow::record_batch::{RecordBatch, RecordBatchOptions};
+use axum::body::Bytes;
+use bson::{to_bson, Bson, Document};
+use futures::{Stream, StreamExt};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use std::sync::Arc;
+
+use crate::error::Error as RestError;
+use crate::queries::{Formats, QueriesFormat};
+
+// Arrow and parquet are written a record batch at a time
+const BATCH_SIZE: usize = 1024;
+
+// Parquet row groups are held in memory until they are full
+const ROW_GROUP_SIZE: usize = 64 * BATCH_SIZE;
+
+// Renders documents as CSV rows, flattening nested fields with dot notation
+#[derive(Debug, Default)]
+pub struct CsvWriter {
+    columns: Option<Vec<String>>,
+    header_written: bool,
+}
+
+impl CsvWriter {
+    // Columns are comma separated, eg: name,address.city
+    // Without explicit columns, the fields of the first document are used
+    pub fn new(columns: Option<&str>) -> Self {
+        let columns = columns.map(|columns| {
+            columns
+                .split(',')
+                .map(|column| column.trim().to_string())
+                .filter(|column| !column.is_empty())
+                .collect()
+        });
+
+        CsvWriter {
+            columns,
+            header_written: false,
+        }
+    }
+
+    // The header can only be written up front if the columns were given explicitly
+    pub fn header(&mut self) -> Option<String> {
+        let columns = self.columns.as_ref()?;
+        self.header_written = true;
+        Some(Self::line(columns.iter().map(|c| c.as_str())))
+    }
+
+    // Returns the csv line for a document, preceded by the header on the first call
+    pub fn row(&mut self, doc: &Document) -> String {
+        let mut fields = Vec::new();
+        flatten("", doc, &mut fields);
+
+        let columns = self
+            .columns
+            .get_or_insert_with(|| fields.iter().map(|(k, _)| k.clone()).collect());
+
+        let values: Vec<String> = columns
+            .iter()
+            .map(|column| {
+                fields
+                    .iter()
+                    .find(|(k, _)| k == column)
+                    .map_or(String::new(), |(_, v)| render(v))
+            })
+            .collect();
+        let row = Self::line(values.iter().map(|v| v.as_str()));
+
+        if self.header_written {
+            row
+        } else {
+            self.header_written = true;
+            let header = Self::line(columns.iter().map(|c| c.as_str()));
+            format!("{}{}", header, row)
+        }
+    }
+
+    fn line<'a>(values: impl Iterator<Item = &'a str>) -> String {
+        let mut line = values.map(escape).collect::<Vec<String>>().join(",");
+        line.push_str("\r\n");
+        line
+    }
+}
+
+fn flatten<'a>(prefix: &str, doc: &'a Document, fields: &mut Vec<(String, &'a Bson)>) {
+    for (key, value) in doc {
+        let key = if prefix.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+
+        match value {
+            Bson::Document(nested) => flatten(&key, nested, fields),
+            _ => fields.push((key, value)),
+        }
+    }
+}
+
+fn render(value: &Bson) -> String {
+    match value {
+        Bson::Null | Bson::Undefined => String::new(),
+        Bson::String(s) => s.clone(),
+        Bson::Boolean(b) => b.to_string(),
+        Bson::Int32(i) => i.to_string(),
+        Bson::Int64(i) => i.to_string(),
+        Bson::Double(d) => d.to_string(),
+        Bson::Decimal128(d) => d.to_string(),
+        Bson::ObjectId(oid) => oid.to_hex(),
+        Bson::DateTime(date) => date
+            .try_to_rfc3339_string()
+            .unwrap_or_else(|_| date.timestamp_millis().to_string()),
+        Bson::Symbol(s) => s.clone(),
+        _ => value.clone().into_relaxed_extjson().to_string(),
+    }
+}
+
+fn escape(value: &str) -> String {
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// How a stream of documents is written out, either a doc at a time or a record batch at a time
+pub enum Output {
+    Rows(RowWriter),
+    Batches(Box<BatchWriter>),
+}
+
+// Renders each document on its own
+pub enum RowWriter {
+    Json,
+    Ejson,
+    Csv(CsvWriter),
+    Bson,
+}
+
+impl Output {
+    pub fn new(queries: &QueriesFormat) -> Result<Self, RestError> {
+        let output = match queries.format.clone() {
+            None | Some(Formats::Json) => Output::Rows(RowWriter::Json),
+            Some(Formats::Ejson) => Output::Rows(RowWriter::Ejson),
+            Some(Formats::Csv) => {
+                Output::Rows(RowWriter::Csv(CsvWriter::new(queries.columns.as_deref())))
+            }
+            Some(Formats::Bson) => Output::Rows(RowWriter::Bson),
+            Some(format @ (Formats::Arrow | Formats::Parquet)) => {
+                Output::Batches(Box::new(BatchWriter::new(format, queries.schema.as_deref())?))
+            }
+        };
+        Ok(output)
+    }
+}
+
+impl RowWriter {
+    // Explicit csv columns let the header go out before the first doc
+    pub fn header(&mut self) -> Option<String> {
+        match self {
+            RowWriter::Csv(csv) => csv.header(),
+            _ => None,
+        }
+    }
+
+    pub fn row(&mut self, doc: &Document) -> Result<Bytes, RestError> {
+        let bytes = match self {
+            RowWriter::Json => to_bson(doc)?.into_relaxed_extjson().to_string().into(),
+            RowWriter::Ejson => to_bson(doc)?.into_canonical_extjson().to_string().into(),
+            RowWriter::Csv(csv) => csv.row(doc).into(),
+            RowWriter::Bson => bson::to_vec(doc)?.into(),
+        };
+        Ok(bytes)
+    }
+}
+
+// Renders documents as Arrow IPC stream or Parquet record batches, flattening nested fields with dot notation
+// The schema is either given by the caller, or inferred from the first batch
+pub struct BatchWriter {
+    format: Formats,
+    schema: Option<SchemaRef>,
+    inferred: bool,
+    writer: Option<Writer>,
+}
+
+enum Writer {
+    Arrow(StreamWriter<Vec<u8>>),
+    Parquet(ArrowWriter<Vec<u8>>),
+}
+
+impl BatchWriter {
+    // Schemas are comma separated fields and their types, eg: name:utf8,address.zip:int64
+    pub fn new(format: Formats, schema: Option<&str>) -> Result<Self, RestError> {
+        let schema = match schema {
+            Some(schema) => Some(Arc::new(parse_schema(schema)?)),
+            None => None,
+        };
+
+        Ok(BatchWriter {
+            format,
+            inferred: schema.is_none(),
+            schema,
+            writer: None,
+        })
+    }
+
+    // Returns the bytes for a batch, preceded by the stream header on the first call
+    pub fn write(&mut self, docs: &[Document]) -> Result<Bytes, RestError> {
+        let schema = self
+            .schema
+            .get_or_insert_with(|| Arc::new(infer_schema(docs)))
+            .clone();
+        if self.inferred {
+            unknown_fields(&schema, docs)?;
+        }
+        let batch = record_batch(&schema, docs)?;
+
+        let writer = self.writer(&schema)?;
+        match writer {
+            Writer::Arrow(ref mut w) => w.write(&batch)?,
+            Writer::Parquet(ref mut w) => w.write(&batch)?,
+        };
+
+        Ok(writer.take())
+    }
+
+    // Returns whatever is left, along with the stream footer
+    pub fn finish(&mut self) -> Result<Bytes, RestError> {
+        let schema = self
+            .schema
+            .get_or_insert_with(|| Arc::new(Schema::empty()))
+            .clone();
+
+        let writer = self.writer(&schema)?;
+        match writer {
+            Writer::Arrow(ref mut w) => w.finish()?,
+            Writer::Parquet(ref mut w) => {
+                w.finish()?;
+            }
+        };
+
+        Ok(writer.take())
+    }
+
+    fn writer(&mut self, schema: &SchemaRef) -> Result<&mut Writer, RestError> {
+        let writer = match self.writer.take() {
+            Some(writer) => writer,
+            None => match self.format {
+                Formats::Parquet => {
+                    let properties = WriterProperties::builder()
+                        .set_compression(Compression::SNAPPY)
+                        .set_max_row_group_size(ROW_GROUP_SIZE)
+                        .build();
+                    Writer::Parquet(ArrowWriter::try_new(
+                        Vec::new(),
+                        schema.clone(),
+                        Some(properties),
+                    )?)
+                }
+                _ => Writer::Arrow(StreamWriter::try_new(Vec::new(), schema)?),
+            },
+        };
+
+        Ok(self.writer.insert(writer))
+    }
+}
+
+impl Writer {
+    // Hand off everything written so far
+    fn take(&mut self) -> Bytes {
+        let buffer = match self {
+            Writer::Arrow(w) => w.get_mut(),
+            Writer::Parquet(w) => w.inner_mut(),
+        };
+        std::mem::take(buffer).into()
+    }
+}
+
+// Documents written out a doc at a time
+pub fn rows<S>(docs: S, mut writer: RowWriter) -> impl Stream<Item = Result<Bytes, RestError>>
+where
+    S: Stream<Item = mongodb::error::Result<Document>> + Send + Unpin + 'static,
+{
+    let header = writer.header().map(|header| Ok(header.into()));
+    let rows = docs.map(move |doc| {
+        let bytes = writer.row(&doc?)?;
+        log::debug!("Found doc: {:?}", bytes);
+        Ok(bytes)
+    });
+    futures::stream::iter(header).chain(rows)
+}
+
+// Documents written out a record batch at a time, stopping at the first error
+pub fn record_batches<S>(
+    docs: S,
+    writer: BatchWriter,
+) -> impl Stream<Item = Result<Bytes, RestError>>
+where
+    S: Stream<Item = mongodb::error::Result<Document>> + Send + Unpin + 'static,
+{
+    let chunks = docs.chunks(BATCH_SIZE);
+
+    futures::stream::unfold(Some((chunks, writer)), |state| async move {
+        let (mut chunks, mut writer) = state?;
+        match chunks.next().await {
+            Some(chunk) => {
+                let bytes = chunk
+                    .into_iter()
+                    .collect::<mongodb::error::Result<Vec<Document>>>()
+                    .map_err(RestError::from)
+                    .and_then(|docs| writer.write(&docs));
+                let state = bytes.is_ok().then_some((chunks, writer));
+                Some((bytes, state))
+            }
+            None => Some((writer.finish(), None)),
+        }
+    })
+}
+
+fn parse_schema(schema: &str) -> Result<Schema, RestError> {
+    let fields = schema
+        .split(',')
+        .map(|field| field.trim())
+        .filter(|field| !field.is_empty())
+        .map(|field| {
+            let (name, data_type) = field.split_once(':').unwrap_or((field, "utf8"));
+            let data_type = match data_type.trim() {
+                "utf8" | "string" => DataType::Utf8,
+                "bool" | "boolean" => DataType::Boolean,
+                "int32" => DataType::Int32,
+                "int64" => DataType::Int64,
+                "float64" | "double" => DataType::Float64,
+                "timestamp" => DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into())),
+                other => {
+                    return Err(RestError::BadRequest(format!(
+                        "Unsupported schema type for {}: {}",
+                        name, other
+                    )))
+                }
+            };
+            Ok(Field::new(name.trim(), data_type, true))
+        })
+        .collect::<Result<Vec<Field>, RestError>>()?;
+
+    Ok(Schema::new(fields))
+}
+
+// Fields are ordered by first appearance, and fields with mixed types fall back to strings
+fn infer_schema(docs: &[Document]) -> Schema {
+    let mut columns: Vec<(String, Option<DataType>)> = Vec::new();
+
+    for doc in docs {
+        let mut fields = Vec::new();
+        flatten("", doc, &mut fields);
+
+        for (key, value) in fields {
+            let data_type = data_type(value);
+            match columns.iter_mut().find(|(k, _)| k == &key) {
+                Some((_, existing)) => *existing = merge(existing.take(), data_type),
+                None => columns.push((key, data_type)),
+            }
+        }
+    }
+
+    let fields: Vec<Field> = columns
+        .into_iter()
+        .map(|(key, data_type)| Field::new(key, data_type.unwrap_or(DataType::Utf8), true))
+        .collect();
+
+    Schema::new(fields)
+}
+
+fn data_type(value: &Bson) -> Option<DataType> {
+    match value {
+        Bson::Null | Bson::Undefined => None,
+        Bson::Boolean(_) => Some(DataType::Boolean),
+        Bson::Int32(_) => Some(DataType::Int32),
+        Bson::Int64(_) => Some(DataType::Int64),
+        Bson::Double(_) => Some(DataType::Float64),
+        Bson::DateTime(_) => Some(DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into()))),
+        _ => Some(DataType::Utf8),
+    }
+}
+
+fn merge(existing: Option<DataType>, next: Option<DataType>) -> Option<DataType> {
+    match (existing, next) {
+        (None, next) => next,
+        (existing, None) => existing,
+        (Some(existing), Some(next)) if existing == next => Some(existing),
+        (Some(DataType::Int32 | DataType::Int64), Some(DataType::Int32 | DataType::Int64)) => {
+            Some(DataType::Int64)
+        }
+        (
+            Some(DataType::Int32 | DataType::Int64 | DataType::Float64),
+            Some(DataType::Int32 | DataType::Int64 | DataType::Float64),
+        ) => Some(DataType::Float64),
+        _ => Some(DataType::Utf8),
+    }
+}
+
+// The first batch fixes an inferred schema, so fields first seen after it can't be added
+fn unknown_fields(schema: &Schema, docs: &[Document]) -> Result<(), RestError> {
+    for doc in docs {
+        let mut fields = Vec::new();
+        flatten("", doc, &mut fields);
+
+        let unknown = fields.iter().find(|(key, value)| {
+            !matches!(value, Bson::Null | Bson::Undefined) && schema.field_with_name(key).is_err()
+        });
+        if let Some((key, _)) = unknown {
+            return Err(RestError::BadRequest(format!(
+                "{} first appears after the schema was inferred, pass a schema that includes it",
+                key
+            )));
+        }
+    }
+    Ok(())
+}
+
+// Values that don't fit their column's type are refused, as earlier batches can't be rewritten
+fn record_batch(schema: &SchemaRef, docs: &[Document]) -> Result<RecordBatch, RestError> {
+    let rows: Vec<Vec<(String, &Bson)>> = docs
+        .iter()
+        .map(|doc| {
+            let mut fields = Vec::new();
+            flatten("", doc, &mut fields);
+            fields
+        })
+        .collect();
+
+    let columns = schema
+        .fields()
+        .iter()
+        .map(|field| {
+            let values = rows.iter().map(|row| {
+                row.iter()
+                    .find(|(k, _)| k == field.name())
+                    .map(|(_, v)| *v)
+            });
+            column(field.name(), field.data_type(), values)
+        })
+        .collect::<Result<Vec<ArrayRef>, RestError>>()?;
+
+    let options = RecordBatchOptions::new().with_row_count(Some(docs.len()));
+    Ok(RecordBatch::try_new_with_options(
+        schema.clone(),
+        columns,
+        &options,
+    )?)
+}
+
+fn column<'a>(
+    name: &str,
+    data_type: &DataType,
+    values: impl Iterator<Item = Option<&'a Bson>>,
+) -> Result<ArrayRef, RestError> {
+    let mismatch = |value: &Bson| {
+        RestError::BadRequest(format!(
+            "{} holds a {:?} value, which doesn't fit its {} column, pass a schema that allows it",
+            name,
+            value.element_type(),
+            data_type
+        ))
+    };
+
+    let array: ArrayRef = match data_type {
+        DataType::Boolean => Arc::new(BooleanArray::from(
+            values
+                .map(|value| match value {
+                    None | Some(Bson::Null) | Some(Bson::Undefined) => Ok(None),
+                    Some(Bson::Boolean(b)) => Ok(Some(*b)),
+                    Some(value) => Err(mismatch(value)),
+                })
+                .collect::<Result<Vec<Option<bool>>, RestError>>()?,
+        )),
+        DataType::Int32 => Arc::new(Int32Array::from(
+            values
+                .map(|value| match value {
+                    None | Some(Bson::Null) | Some(Bson::Undefined) => Ok(None),
+                    Some(Bson::Int32(i)) => Ok(Some(*i)),
+                    Some(value) => Err(mismatch(value)),
+                })
+                .collect::<Result<Vec<Option<i32>>, RestError>>()?,
+        )),
+        DataType::Int64 => Arc::new(Int64Array::from(
+            values
+                .map(|value| match value {
+                    None | Some(Bson::Null) | Some(Bson::Undefined) => Ok(None),
+                    Some(Bson::Int32(i)) => Ok(Some(*i as i64)),
+                    Some(Bson::Int64(i)) => Ok(Some(*i)),
+                    Some(value) => Err(mismatch(value)),
+                })
+                .collect::<Result<Vec<Option<i64>>, RestError>>()?,
+        )),
+        DataType::Float64 => Arc::new(Float64Array::from(
+            values
+                .map(|value| match value {
+                    None | Some(Bson::Null) | Some(Bson::Undefined) => Ok(None),
+                    Some(Bson::Int32(i)) => Ok(Some(*i as f64)),
+                    Some(Bson::Int64(i)) => Ok(Some(*i as f64)),
+                    Some(Bson::Double(d)) => Ok(Some(*d)),
+                    Some(value) => Err(mismatch(value)),
+                })
+                .collect::<Result<Vec<Option<f64>>, RestError>>()?,
+        )),
+        DataType::Timestamp(_, timezone) => Arc::new(
+            TimestampMillisecondArray::from(
+                values
+                    .map(|value| match value {
+                        None | Some(Bson::Null) | Some(Bson::Undefined) => Ok(None),
+                        Some(Bson::DateTime(date)) => Ok(Some(date.timestamp_millis())),
+                        Some(value) => Err(mismatch(value)),
+                    })
+                    .collect::<Result<Vec<Option<i64>>, RestError>>()?,
+            )
+            .with_timezone_opt(timezone.clone()),
+        ),
+        // Anything can be rendered as a string
+        _ => Arc::new(
+            values
+                .map(|value| match value {
+                    None | Some(Bson::Null) | Some(Bson::Undefined) => None,
+                    Some(value) => Some(render(value)),
+                })
+                .collect::<StringArray>(),
+        ),
+    };
+    Ok(array)
+}
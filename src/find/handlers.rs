@@ -1,18 +1,18 @@
 use axum::{
-    body::Bytes,
-    body::StreamBody,
     extract::{Path, Query},
+    response::IntoResponse,
     Extension, Json,
 };
 use bson::{doc, to_document};
-use futures::Stream;
 use serde_json::{json, Value};
 
 use mongodb::options::FindOptions;
 
+use crate::body::Payload;
 use crate::error::Error as RestError;
 use crate::find::structs::{Distinct, Explain, Find, FindOne, FindRaw, Count};
-use crate::queries::{ExplainFormat, QueriesFormat};
+use crate::framing::Framing;
+use crate::queries::{ExplainFormat, QueriesConcern, QueriesFormat, QueriesPage};
 use crate::scopes::AuthorizeScope;
 use crate::State;
 
@@ -21,12 +21,12 @@ pub async fn find_explain(
     Extension(scopes): Extension<AuthorizeScope>,
     Path((db, coll)): Path<(String, String)>,
     queries: Query<ExplainFormat>,
-    Json(payload): Json<Find>,
+    Payload(payload): Payload<Find>,
 ) -> Result<Json<Value>, RestError> {
     log::info!("{{\"fn\": \"find_explain\", \"db\": \"{}\", \"coll\": \"{}\"}}", &db, &coll);
 
     // Validate that the client has access to this database
-    scopes.read(&db)?;
+    scopes.read(&db, Some(&coll))?;
 
     let find_raw = FindRaw {
         find: coll.to_string(),
@@ -64,9 +64,11 @@ pub async fn find_latest_ten(
     Extension(state): Extension<State>,
     Path((db, coll)): Path<(String, String)>,
     queries: Query<QueriesFormat>,
-) -> Result<StreamBody<impl Stream<Item = Result<Bytes, RestError>>>, RestError> {
+    framing: Framing,
+    concern: Query<QueriesConcern>,
+) -> Result<impl IntoResponse, RestError> {
     // Validate that the client has access to this database
-    scopes.read(&db)?;
+    scopes.read(&db, Some(&coll))?;
 
     log::info!("{{\"fn\": \"find\", \"db\": \"{}\", \"coll\": \"{}\"}}", &db, &coll);
     let payload = Find {
@@ -78,7 +80,7 @@ pub async fn find_latest_ten(
                 .build(),
         ),
     };
-    state.db.find(&db, &coll, payload.into(), queries).await
+    state.db.find(&db, &coll, payload.into(), queries, framing, &concern, &scopes).await
 }
 
 pub async fn find_latest_one(
@@ -86,9 +88,11 @@ pub async fn find_latest_one(
     Extension(state): Extension<State>,
     Path((db, coll)): Path<(String, String)>,
     queries: Query<QueriesFormat>,
-) -> Result<StreamBody<impl Stream<Item = Result<Bytes, RestError>>>, RestError> {
+    framing: Framing,
+    concern: Query<QueriesConcern>,
+) -> Result<impl IntoResponse, RestError> {
     // Validate that the client has access to this database
-    scopes.read(&db)?;
+    scopes.read(&db, Some(&coll))?;
 
     log::info!("{{\"fn\": \"find\", \"db\": \"{}\", \"coll\": \"{}\"}}", &db, &coll);
     let payload = Find {
@@ -100,7 +104,7 @@ pub async fn find_latest_one(
                 .build(),
         ),
     };
-    state.db.find(&db, &coll, payload.into(), queries).await
+    state.db.find(&db, &coll, payload.into(), queries, framing, &concern, &scopes).await
 }
 
 pub async fn find(
@@ -108,28 +112,63 @@ pub async fn find(
     Extension(state): Extension<State>,
     Path((db, coll)): Path<(String, String)>,
     queries: Query<QueriesFormat>,
-    Json(payload): Json<Find>,
-) -> Result<StreamBody<impl Stream<Item = Result<Bytes, RestError>>>, RestError> {
+    framing: Framing,
+    concern: Query<QueriesConcern>,
+    Payload(payload): Payload<Find>,
+) -> Result<impl IntoResponse, RestError> {
     // Validate that the client has access to this database
-    scopes.read(&db)?;
+    scopes.read(&db, Some(&coll))?;
 
     log::info!("{{\"fn\": \"find\", \"db\": \"{}\", \"coll\": \"{}\"}}", &db, &coll);
-    state.db.find(&db, &coll, payload, queries).await
+    state.db.find(&db, &coll, payload, queries, framing, &concern, &scopes).await
+}
+
+pub async fn find_page(
+    Extension(scopes): Extension<AuthorizeScope>,
+    Extension(state): Extension<State>,
+    Path((db, coll)): Path<(String, String)>,
+    queries: Query<QueriesPage>,
+    concern: Query<QueriesConcern>,
+    Payload(payload): Payload<Find>,
+) -> Result<Json<Value>, RestError> {
+    // Validate that the client has access to this database
+    scopes.read(&db, Some(&coll))?;
+
+    log::info!("{{\"fn\": \"find_page\", \"db\": \"{}\", \"coll\": \"{}\"}}", &db, &coll);
+    Ok(Json(json!(
+        state.db.find_page(&db, &coll, payload, &queries, &concern, &scopes).await?
+    )))
+}
+
+pub async fn cursor_next(
+    Extension(scopes): Extension<AuthorizeScope>,
+    Extension(state): Extension<State>,
+    Path((db, coll, id)): Path<(String, String, String)>,
+    queries: Query<QueriesPage>,
+) -> Result<Json<Value>, RestError> {
+    // Validate that the client has access to this database
+    scopes.read(&db, Some(&coll))?;
+
+    log::info!("{{\"fn\": \"cursor_next\", \"db\": \"{}\", \"coll\": \"{}\"}}", &db, &coll);
+    Ok(Json(json!(
+        state.db.cursor_next(&db, &coll, &id, &queries, &scopes).await?
+    )))
 }
 
 pub async fn count(
     Extension(scopes): Extension<AuthorizeScope>,
     Extension(state): Extension<State>,
     Path((db, coll)): Path<(String, String)>,
-    Json(payload): Json<Count>,
+    concern: Query<QueriesConcern>,
+    Payload(payload): Payload<Count>,
 ) -> Result<Json<Value>, RestError> {
     // Validate that the client has access to this database
-    scopes.read(&db)?;
+    scopes.read(&db, Some(&coll))?;
 
     log::info!("{{\"fn\": \"find_one\", \"db\": \"{}\", \"coll\": \"{}\"}}", &db, &coll);
     
     Ok(Json(json!(
-        state.db.count(&db, &coll, payload).await?
+        state.db.count(&db, &coll, payload, &concern, &scopes).await?
     )))
 }
 
@@ -138,14 +177,15 @@ pub async fn find_one(
     Extension(state): Extension<State>,
     Path((db, coll)): Path<(String, String)>,
     queries: Query<QueriesFormat>,
-    Json(payload): Json<FindOne>,
+    concern: Query<QueriesConcern>,
+    Payload(payload): Payload<FindOne>,
 ) -> Result<Json<Value>, RestError> {
     // Validate that the client has access to this database
-    scopes.read(&db)?;
+    scopes.read(&db, Some(&coll))?;
 
     log::info!("{{\"fn\": \"find_one\", \"db\": \"{}\", \"coll\": \"{}\"}}", &db, &coll);
     Ok(Json(json!(
-        state.db.find_one(&db, &coll, payload, &queries).await?
+        state.db.find_one(&db, &coll, payload, &queries, &concern, &scopes).await?
     )))
 }
 
@@ -154,13 +194,14 @@ pub async fn distinct(
     Extension(state): Extension<State>,
     Path((db, coll)): Path<(String, String)>,
     queries: Query<QueriesFormat>,
-    Json(payload): Json<Distinct>,
+    concern: Query<QueriesConcern>,
+    Payload(payload): Payload<Distinct>,
 ) -> Result<Json<Value>, RestError> {
     // Validate that the client has access to this database
-    scopes.read(&db)?;
+    scopes.read(&db, Some(&coll))?;
 
     log::info!("{{\"fn\": \"distinct\", \"db\": \"{}\", \"coll\": \"{}\"}}", &db, &coll);
     Ok(Json(json!(
-        state.db.distinct(&db, &coll, payload, &queries).await?
+        state.db.distinct(&db, &coll, payload, &queries, &concern, &scopes).await?
     )))
 }
@@ -0,0 +1,207 @@
+use axum::{
+    async_trait,
+    body::{Bytes, StreamBody},
+    extract::FromRequestParts,
+    http::{header::ACCEPT, header::CONTENT_TYPE, request::Parts, HeaderName},
+};
+use bson::Bson;
+use futures::{stream, Stream, StreamExt};
+use mongodb::change_stream::event::ResumeToken;
+use serde_json::Value;
+use std::convert::Infallible;
+
+use crate::body::BSON_CONTENT_TYPE;
+use crate::error::Error as RestError;
+use crate::queries::Formats;
+
+type Result<T> = std::result::Result<T, RestError>;
+
+const LAST_EVENT_ID: &str = "last-event-id";
+
+pub const ARROW_CONTENT_TYPE: &str = "application/vnd.apache.arrow.stream";
+pub const PARQUET_CONTENT_TYPE: &str = "application/vnd.apache.parquet";
+
+// How streamed json docs are separated, negotiated from the Accept header
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Framing {
+    #[default]
+    Ndjson,
+    Array,
+    EventStream,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Framing
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &S,
+    ) -> std::result::Result<Self, Self::Rejection> {
+        let accept = match parts
+            .headers
+            .get(ACCEPT)
+            .and_then(|header| header.to_str().ok())
+        {
+            Some(accept) => accept,
+            None => return Ok(Framing::default()),
+        };
+
+        // Take the first media type we know how to produce, ignoring any parameters
+        let framing = accept
+            .split(',')
+            .filter_map(|media| media.split(';').next())
+            .find_map(|media| match media.trim() {
+                "application/x-ndjson" => Some(Framing::Ndjson),
+                "application/json" => Some(Framing::Array),
+                "text/event-stream" => Some(Framing::EventStream),
+                _ => None,
+            });
+
+        Ok(framing.unwrap_or_default())
+    }
+}
+
+impl Framing {
+    fn content_type(&self) -> &'static str {
+        match self {
+            Framing::Ndjson => "application/x-ndjson",
+            Framing::Array => "application/json",
+            Framing::EventStream => "text/event-stream",
+        }
+    }
+}
+
+// Frame a stream of docs for the response, setting the matching Content-Type
+// Csv, bson, arrow and parquet docs are already framed, so only json docs are wrapped
+pub fn respond<S>(
+    docs: S,
+    format: &Option<Formats>,
+    framing: Framing,
+) -> (
+    [(HeaderName, &'static str); 1],
+    StreamBody<impl Stream<Item = Result<Bytes>>>,
+)
+where
+    S: Stream<Item = Result<Bytes>> + Send + 'static,
+{
+    let (content_type, framing) = match format {
+        Some(Formats::Csv) => ("text/csv", None),
+        Some(Formats::Bson) => (BSON_CONTENT_TYPE, None),
+        Some(Formats::Arrow) => (ARROW_CONTENT_TYPE, None),
+        Some(Formats::Parquet) => (PARQUET_CONTENT_TYPE, None),
+        None | Some(Formats::Json) | Some(Formats::Ejson) => {
+            (framing.content_type(), Some(framing))
+        }
+    };
+
+    let (open, close) = match framing {
+        Some(Framing::Array) => (Some(Ok(Bytes::from("["))), Some(Ok(Bytes::from("]")))),
+        _ => (None, None),
+    };
+
+    let docs = docs.enumerate().map(move |(i, doc)| {
+        let doc = doc?;
+        let (prefix, suffix) = match framing {
+            None => return Ok(doc),
+            Some(Framing::Ndjson) => ("", "\n"),
+            Some(Framing::Array) if i == 0 => ("", ""),
+            Some(Framing::Array) => (",", ""),
+            Some(Framing::EventStream) => return Ok(event(None, None, &String::from_utf8_lossy(&doc))),
+        };
+
+        let mut framed = Vec::with_capacity(prefix.len() + doc.len() + suffix.len());
+        framed.extend_from_slice(prefix.as_bytes());
+        framed.extend_from_slice(&doc);
+        framed.extend_from_slice(suffix.as_bytes());
+        Ok(framed.into())
+    });
+
+    let body = stream::iter(open).chain(docs).chain(stream::iter(close));
+
+    ([(CONTENT_TYPE, content_type)], StreamBody::new(body))
+}
+
+// Stream of already framed Server-Sent Events
+pub fn events<S>(
+    events: S,
+) -> (
+    [(HeaderName, &'static str); 1],
+    StreamBody<impl Stream<Item = Result<Bytes>>>,
+)
+where
+    S: Stream<Item = Result<Bytes>> + Send + 'static,
+{
+    ([(CONTENT_TYPE, "text/event-stream")], StreamBody::new(events))
+}
+
+// Frame a single Server-Sent Event, leaving out the event name for plain messages
+pub fn event(name: Option<&str>, id: Option<&str>, data: &str) -> Bytes {
+    let mut event = String::new();
+    if let Some(name) = name {
+        event.push_str(&format!("event: {}\n", name));
+    }
+    if let Some(id) = id {
+        event.push_str(&format!("id: {}\n", id));
+    }
+    for line in data.lines() {
+        event.push_str(&format!("data: {}\n", line));
+    }
+    event.push('\n');
+    event.into()
+}
+
+// The id of the last event an EventSource client saw before reconnecting
+#[derive(Clone, Debug, Default)]
+pub struct LastEventId(pub Option<String>);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for LastEventId
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &S,
+    ) -> std::result::Result<Self, Self::Rejection> {
+        let id = parts
+            .headers
+            .get(LAST_EVENT_ID)
+            .and_then(|header| header.to_str().ok())
+            .filter(|id| !id.is_empty())
+            .map(|id| id.to_string());
+
+        Ok(LastEventId(id))
+    }
+}
+
+impl LastEventId {
+    // Change stream event ids are their resume tokens, sent as relaxed extended json
+    pub fn resume_token(&self) -> Result<Option<ResumeToken>> {
+        let id = match self.0 {
+            Some(ref id) => id,
+            None => return Ok(None),
+        };
+
+        match resume_token(id) {
+            Some(token) => Ok(Some(token)),
+            None => Err(RestError::BadRequest(format!(
+                "Invalid Last-Event-ID: {}",
+                id
+            ))),
+        }
+    }
+}
+
+// Resume tokens are passed around as relaxed extended json
+pub fn resume_token(token: &str) -> Option<ResumeToken> {
+    serde_json::from_str::<Value>(token)
+        .ok()
+        .and_then(|value| Bson::try_from(value).ok())
+        .and_then(|token| bson::from_bson(token).ok())
+}
@@ -1,21 +1,26 @@
 use axum::{
-    body::{Bytes, StreamBody},
     extract::{Path, Query},
+    response::IntoResponse,
     Extension, Json,
 };
-use futures::Stream;
 use serde_json::{json, Value};
 
 use crate::error::Error as RestError;
+use crate::framing::Framing;
 use crate::index::structs::Index;
 use crate::queries::{QueriesDelete, QueriesFormat};
+use crate::scopes::AuthorizeScope;
 use crate::State;
 
 pub async fn index_delete(
     Extension(state): Extension<State>,
+    Extension(scopes): Extension<AuthorizeScope>,
     Path((db, coll)): Path<(String, String)>,
     queries: Query<QueriesDelete>,
 ) -> Result<Json<Value>, RestError> {
+    // Validate that the client has access to this database
+    scopes.dbadmin(&db, Some(&coll))?;
+
     log::info!("{{\"fn\": \"index_create\", \"db\": \"{}\", \"coll\": \"{}\"}}", &db, &coll);
     Ok(Json(json!(
         state.db.index_delete(&db, &coll, &queries).await?
@@ -24,9 +29,13 @@ pub async fn index_delete(
 
 pub async fn index_create(
     Extension(state): Extension<State>,
+    Extension(scopes): Extension<AuthorizeScope>,
     Path((db, coll)): Path<(String, String)>,
     Json(payload): Json<Index>,
 ) -> Result<Json<Value>, RestError> {
+    // Validate that the client has access to this database
+    scopes.dbadmin(&db, Some(&coll))?;
+
     log::info!("{{\"fn\": \"index_create\", \"db\": \"{}\", \"coll\": \"{}\"}}", &db, &coll);
     Ok(Json(json!(
         state.db.index_create(&db, &coll, payload).await?
@@ -35,9 +44,13 @@ pub async fn index_create(
 
 pub async fn indexes(
     Extension(state): Extension<State>,
+    Extension(scopes): Extension<AuthorizeScope>,
     queries: Query<QueriesFormat>,
     Path((db, coll)): Path<(String, String)>,
 ) -> Result<Json<Value>, RestError> {
+    // Validate that the client has access to this database
+    scopes.read(&db, Some(&coll))?;
+
     log::info!("{{\"fn\": \"coll_indexes\", \"db\": \"{}\", \"coll\": \"{}\"}}", &db, &coll);
     Ok(Json(json!(
         state.db.coll_indexes(&db, &coll, &queries).await?
@@ -46,8 +59,13 @@ pub async fn indexes(
 
 pub async fn index_stats(
     Extension(state): Extension<State>,
+    Extension(scopes): Extension<AuthorizeScope>,
     Path((db, coll)): Path<(String, String)>,
-) -> Result<StreamBody<impl Stream<Item = Result<Bytes, RestError>>>, RestError> {
+    framing: Framing,
+) -> Result<impl IntoResponse, RestError> {
+    // Validate that the client has access to this database
+    scopes.read(&db, Some(&coll))?;
+
     log::info!("{{\"fn\": \"coll_indexes\", \"db\": \"{}\", \"coll\": \"{}\"}}", &db, &coll);
-    state.db.coll_index_stats(&db, &coll).await
+    state.db.coll_index_stats(&db, &coll, framing).await
 }
@@ -1,28 +1,33 @@
 use axum::{
-    extract::{Path, Query},
+    extract::{BodyStream, Path, Query},
     Extension, Json,
 };
 use bson::Document;
 use serde_json::{json, Value};
 
+use crate::body::{Documents, Input, Payload};
 use crate::error::Error as RestError;
 use crate::insert::structs::{CustomInsertManyOptions, CustomInsertOneOptions};
+use crate::queries::{QueriesConcern, QueriesImport};
 use crate::scopes::AuthorizeScope;
+use crate::sessions::Transaction;
 use crate::State;
 
 pub async fn insert_many(
     Extension(state): Extension<State>,
     Extension(scopes): Extension<AuthorizeScope>,
     Path((db, coll)): Path<(String, String)>,
+    transaction: Transaction,
     queries: Query<CustomInsertManyOptions>,
-    Json(body): Json<Vec<Document>>,
+    concern: Query<QueriesConcern>,
+    Documents(body): Documents,
 ) -> Result<Json<Value>, RestError> {
     // Validate that the client has access to this database
-    scopes.write(&db)?;
+    scopes.write(&db, Some(&coll))?;
 
     log::info!("{{\"fn\": \"insert\", \"db\": \"{}\", \"coll\": \"{}\"}}", &db, &coll);
     Ok(Json(json!(
-        state.db.insert_many(&db, &coll, body, queries).await?
+        state.db.insert_many(&db, &coll, body, queries, &concern, transaction).await?
     )))
 }
 
@@ -30,14 +35,37 @@ pub async fn insert_one(
     Extension(state): Extension<State>,
     Extension(scopes): Extension<AuthorizeScope>,
     Path((db, coll)): Path<(String, String)>,
+    transaction: Transaction,
     queries: Query<CustomInsertOneOptions>,
-    Json(body): Json<Document>,
+    concern: Query<QueriesConcern>,
+    Payload(body): Payload<Document>,
 ) -> Result<Json<Value>, RestError> {
     // Validate that the client has access to this database
-    scopes.write(&db)?;
+    scopes.write(&db, Some(&coll))?;
 
     log::info!("{{\"fn\": \"insert_one\", \"db\": \"{}\", \"coll\": \"{}\"}}", &db, &coll);
     Ok(Json(json!(
-        state.db.insert_one(&db, &coll, body, queries).await?
+        state.db.insert_one(&db, &coll, body, queries, &concern, transaction).await?
+    )))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn import(
+    Extension(state): Extension<State>,
+    Extension(scopes): Extension<AuthorizeScope>,
+    Path((db, coll)): Path<(String, String)>,
+    transaction: Transaction,
+    queries: Query<CustomInsertManyOptions>,
+    import: Query<QueriesImport>,
+    input: Input,
+    concern: Query<QueriesConcern>,
+    body: BodyStream,
+) -> Result<Json<Value>, RestError> {
+    // Validate that the client has access to this database
+    scopes.write(&db, Some(&coll))?;
+
+    log::info!("{{\"fn\": \"import\", \"db\": \"{}\", \"coll\": \"{}\"}}", &db, &coll);
+    Ok(Json(json!(
+        state.db.import(&db, &coll, body, queries, &import, input, &concern, transaction).await?
     )))
 }
@@ -0,0 +1,352 @@
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::auth::Claims;
+use crate::error::Error as RestError;
+use crate::Args;
+
+type MyResult<T> = std::result::Result<T, RestError>;
+
+// A trusted token issuer, as listed in the --issuers file, eg:
+//
+// - issuer: https://sso.example.com/
+//   audiences: [mongodb-rest]
+// - issuer: https://m2m.example.com/
+//   jwks: https://m2m.example.com/keys
+//   audiences: [mongodb-rest, batch]
+//   claims:
+//     subject: client_id
+//     scopes:
+//       - claim: scope
+//         format: string
+//     optional: [jti]
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct IssuerConfig {
+    // Discovers the jwks url when jwks is missing, and is enforced as the token iss
+    pub issuer: Option<String>,
+    pub jwks: Option<String>,
+    pub audiences: Vec<String>,
+    #[serde(default)]
+    pub claims: ClaimMapping,
+}
+
+// Which token claims hold the values AuthorizeScope needs, and which of them may be left out
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClaimMapping {
+    pub subject: String,
+    pub scopes: Vec<ScopeClaim>,
+    pub optional: Vec<String>,
+}
+
+// A claim to collect scopes from, eg:
+//
+// - claim: groups
+//   prefix: "mongo-"
+//
+// turns the group mongo-sales:read into the scope mongodb.role.sales:read
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ScopeClaim {
+    pub claim: String,
+    #[serde(default)]
+    pub format: ScopeFormat,
+    // Rewritten to mongodb.role. on the values that start with it, other values are dropped
+    pub prefix: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+pub enum ScopeFormat {
+    // ["mongodb.role.sales:read", "mongodb.cluster.rs0:allow"]
+    #[default]
+    #[serde(rename = "array")]
+    Array,
+    // "mongodb.role.sales:read mongodb.cluster.rs0:allow"
+    #[serde(rename = "string")]
+    String,
+}
+
+impl Default for ClaimMapping {
+    fn default() -> Self {
+        ClaimMapping {
+            subject: String::from("sub"),
+            scopes: vec![ScopeClaim {
+                claim: String::from("scp"),
+                format: ScopeFormat::Array,
+                prefix: None,
+            }],
+            optional: Vec::new(),
+        }
+    }
+}
+
+impl ClaimMapping {
+    pub fn claims(&self, token: &Value) -> MyResult<Claims> {
+        // Transactions and cursors belong to the subject, so it can't be left out
+        let sub = match token[&self.subject].as_str() {
+            Some(sub) => sub.to_string(),
+            None => {
+                log::debug!("\"Token is missing the {} claim\"", self.subject);
+                return Err(RestError::JwtDecode);
+            }
+        };
+
+        let jti = match token["jti"].as_str() {
+            Some(jti) => jti.to_string(),
+            None => self.missing("jti")?.unwrap_or_default(),
+        };
+
+        let mut scp = Vec::new();
+        for scopes in &self.scopes {
+            scp.extend(scopes.scopes(token, self)?);
+        }
+
+        Ok(Claims {
+            sub,
+            exp: token["exp"].as_i64(),
+            iss: token["iss"].as_str().map(|iss| iss.to_string()),
+            jti,
+            scp,
+        })
+    }
+
+    fn is_optional(&self, claim: &str) -> bool {
+        self.optional.iter().any(|optional| optional == claim)
+    }
+
+    // A missing claim is only an error when it hasn't been marked optional
+    fn missing<T>(&self, claim: &str) -> MyResult<Option<T>> {
+        if self.is_optional(claim) {
+            log::trace!("\"Token is missing the optional {} claim\"", claim);
+            Ok(None)
+        } else {
+            log::debug!("\"Token is missing the {} claim\"", claim);
+            Err(RestError::JwtDecode)
+        }
+    }
+}
+
+impl ScopeClaim {
+    fn scopes(&self, token: &Value, mapping: &ClaimMapping) -> MyResult<Vec<String>> {
+        let scopes: Vec<String> = match (&token[&self.claim], &self.format) {
+            (Value::Array(scopes), ScopeFormat::Array) => scopes
+                .iter()
+                .filter_map(|scope| scope.as_str())
+                .map(|scope| scope.to_string())
+                .collect(),
+            (Value::String(scopes), ScopeFormat::String) => scopes
+                .split_whitespace()
+                .map(|scope| scope.to_string())
+                .collect(),
+            (Value::Null, _) => return Ok(mapping.missing(&self.claim)?.unwrap_or_default()),
+            (_, format) => {
+                log::debug!("\"Token {} claim is not in {:?} format\"", self.claim, format);
+                return Err(RestError::JwtDecode);
+            }
+        };
+
+        Ok(match self.prefix {
+            Some(ref prefix) => scopes
+                .into_iter()
+                .filter_map(|scope| {
+                    scope
+                        .strip_prefix(prefix.as_str())
+                        .map(|role| format!("mongodb.role.{}", role))
+                })
+                .collect(),
+            None => scopes,
+        })
+    }
+}
+
+// Issuers come from the --issuers file, or from the single issuer given by --jwks, --issuer and --audience
+pub fn load(args: &Args) -> MyResult<Vec<IssuerConfig>> {
+    let issuers = match args.issuers {
+        Some(ref path) => {
+            let contents = std::fs::read_to_string(path)
+                .map_err(|e| RestError::Config(format!("Unable to read {}: {}", path, e)))?;
+            serde_yaml::from_str::<Vec<IssuerConfig>>(&contents)
+                .map_err(|e| RestError::Config(format!("Unable to parse {}: {}", path, e)))?
+        }
+        None => vec![IssuerConfig {
+            issuer: args.issuer.clone(),
+            jwks: args.jwks.clone(),
+            audiences: args.audience.clone().into_iter().collect(),
+            claims: ClaimMapping::default(),
+        }],
+    };
+
+    for issuer in &issuers {
+        if issuer.issuer.is_none() && issuer.jwks.is_none() {
+            return Err(RestError::Config(
+                "Every issuer needs either an issuer or a jwks url".to_string(),
+            ));
+        }
+
+        // Without these a token could be replayed forever, used by another client, or
+        // meant for another api or issuer
+        for claim in ["exp", "aud", "iss", issuer.claims.subject.as_str()] {
+            if issuer.claims.is_optional(claim) {
+                return Err(RestError::Config(format!(
+                    "Issuer {} can't make the {} claim optional",
+                    issuer.name(),
+                    claim
+                )));
+            }
+        }
+
+        if issuer.audiences.is_empty() {
+            return Err(RestError::Config(format!(
+                "Issuer {} has no audiences",
+                issuer.name()
+            )));
+        }
+    }
+
+    Ok(issuers)
+}
+
+impl IssuerConfig {
+    // Used to tell issuers apart in logs and metrics
+    pub fn name(&self) -> String {
+        self.issuer
+            .clone()
+            .or_else(|| self.jwks.clone())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn mapping(scopes: Vec<ScopeClaim>) -> ClaimMapping {
+        ClaimMapping {
+            scopes,
+            ..ClaimMapping::default()
+        }
+    }
+
+    fn scope_claim(claim: &str, format: ScopeFormat, prefix: Option<&str>) -> ScopeClaim {
+        ScopeClaim {
+            claim: claim.to_string(),
+            format,
+            prefix: prefix.map(|prefix| prefix.to_string()),
+        }
+    }
+
+    // Loads issuers from a yaml file, as --issuers would
+    fn load_yaml(name: &str, yaml: &str) -> MyResult<Vec<IssuerConfig>> {
+        let path =
+            std::env::temp_dir().join(format!("issuers-{}-{}.yaml", name, std::process::id()));
+        std::fs::write(&path, yaml).unwrap();
+        let args = Args {
+            port: 8080,
+            uri: "mongodb://localhost".to_string(),
+            username: None,
+            password: None,
+            readonly: false,
+            replicaset: None,
+            noauth: false,
+            jwks: None,
+            issuer: None,
+            audience: None,
+            issuers: Some(path.to_string_lossy().to_string()),
+            redaction: None,
+            transaction_timeout: 60,
+            cursor_timeout: 300,
+            compression: Vec::new(),
+            compression_min_size: 1024,
+        };
+        let issuers = load(&args);
+        std::fs::remove_file(&path).ok();
+        issuers
+    }
+
+    #[test]
+    fn claims_reads_array_and_string_scopes() {
+        let token = json!({
+            "sub": "client",
+            "exp": 1,
+            "jti": "id",
+            "scp": ["mongodb.role.sales:read"],
+            "scope": "mongodb.role.hr:read  mongodb.cluster.rs0:allow",
+        });
+        let claims = mapping(vec![
+            scope_claim("scp", ScopeFormat::Array, None),
+            scope_claim("scope", ScopeFormat::String, None),
+        ])
+        .claims(&token)
+        .unwrap();
+
+        assert_eq!(claims.sub, "client");
+        assert_eq!(claims.exp, Some(1));
+        assert_eq!(
+            claims.scp,
+            vec![
+                "mongodb.role.sales:read",
+                "mongodb.role.hr:read",
+                "mongodb.cluster.rs0:allow"
+            ]
+        );
+
+        // A claim in the other format is refused rather than read as no scopes
+        let mapping = mapping(vec![scope_claim("scope", ScopeFormat::Array, None)]);
+        assert!(mapping.claims(&token).is_err());
+    }
+
+    #[test]
+    fn prefix_rewrites_and_drops_other_values() {
+        let token = json!({"sub": "client", "jti": "id", "groups": ["mongo-sales:read", "admins", "mongodb.role.hr:read"]});
+        let claims = mapping(vec![scope_claim(
+            "groups",
+            ScopeFormat::Array,
+            Some("mongo-"),
+        )])
+        .claims(&token)
+        .unwrap();
+        assert_eq!(claims.scp, vec!["mongodb.role.sales:read"]);
+    }
+
+    #[test]
+    fn claims_require_sub_and_non_optional_claims() {
+        let mapping = ClaimMapping::default();
+        assert!(mapping.claims(&json!({"jti": "id", "scp": []})).is_err());
+        assert!(mapping
+            .claims(&json!({"sub": "client", "scp": []}))
+            .is_err());
+        assert!(mapping
+            .claims(&json!({"sub": "client", "jti": "id"}))
+            .is_err());
+
+        let optional = ClaimMapping {
+            optional: vec!["jti".to_string(), "scp".to_string()],
+            ..ClaimMapping::default()
+        };
+        let claims = optional.claims(&json!({"sub": "client"})).unwrap();
+        assert!(claims.jti.is_empty() && claims.scp.is_empty());
+    }
+
+    #[test]
+    fn load_refuses_optional_exp() {
+        let issuers = load_yaml(
+            "exp",
+            "- issuer: https://sso.example.com/\n  audiences: [api]\n  claims:\n    optional: [exp]\n",
+        );
+        assert!(matches!(issuers, Err(RestError::Config(message)) if message.contains("exp")));
+
+        let issuers = load_yaml(
+            "sub",
+            "- issuer: https://sso.example.com/\n  audiences: [api]\n  claims:\n    subject: client_id\n    optional: [client_id]\n",
+        );
+        assert!(issuers.is_err());
+
+        let issuers = load_yaml(
+            "jti",
+            "- issuer: https://sso.example.com/\n  audiences: [api]\n  claims:\n    optional: [jti]\n",
+        );
+        assert_eq!(issuers.unwrap().len(), 1);
+    }
+}
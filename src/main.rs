@@ -1,4 +1,5 @@
 use axum::{
+    error_handling::HandleErrorLayer,
     extract::{DefaultBodyLimit, Extension},
     middleware,
     routing::{delete, get, post},
@@ -11,45 +12,72 @@ use log::LevelFilter;
 use std::future::ready;
 use std::io::Write;
 use std::net::SocketAddr;
+use tower::ServiceBuilder;
+use tower_http::decompression::RequestDecompressionLayer;
 use tower_http::trace::TraceLayer;
 use jemallocator::Jemalloc;
 
 mod aggregate;
 mod auth;
+mod body;
+mod bulk;
+mod compression;
+mod cursors;
 mod database;
 mod db;
 mod delete;
+mod document;
 mod error;
+mod export;
 mod find;
+mod framing;
 mod handlers;
 mod https;
 mod index;
 mod insert;
+mod issuers;
 mod metrics;
 mod queries;
+mod redaction;
 mod roles;
+mod schema;
 mod scopes;
+mod sessions;
 mod state;
+mod transaction;
 mod update;
 mod watch;
+mod ws;
 
 use crate::metrics::{setup_metrics_recorder, track_metrics};
 use handlers::{handler_404, health, root};
 
-use aggregate::handlers::{aggregate, aggregate_explain};
+use aggregate::handlers::{aggregate, aggregate_explain, aggregate_page};
 use auth::{auth, AuthJwks};
+use bulk::handlers::bulk_write;
 use database::handlers::{
     coll_count, coll_stats, databases, db_colls, db_stats, rs_conn, rs_log, rs_operations, rs_pool,
     rs_stats, rs_status, rs_top, token_roles, lock_info, rs_config, host_info
 };
-use delete::handlers::{delete_many, delete_one};
-use find::handlers::{distinct, find, find_explain, find_latest_one, find_latest_ten, find_one, count};
+use delete::handlers::{delete_many, delete_one, find_one_and_delete};
+use document::handlers::{document_delete, document_get, document_replace, document_update};
+use find::handlers::{
+    count, cursor_next, distinct, find, find_explain, find_latest_one, find_latest_ten, find_one,
+    find_page,
+};
 use index::handlers::{index_create, index_delete, index_stats, indexes};
-use insert::handlers::{insert_many, insert_one};
+use insert::handlers::{import, insert_many, insert_one};
 use roles::handlers::{create_role, drop_role, get_role, get_roles};
+use schema::handlers::schema;
 use state::State;
-use update::handlers::{update_many, update_one};
-use watch::handlers::{watch, watch_latest};
+use transaction::handlers::{abort_transaction, commit_transaction, start_transaction};
+use update::handlers::{
+    find_one_and_replace, find_one_and_update, replace_one, update_many, update_one,
+};
+use watch::handlers::{
+    watch, watch_cluster, watch_cluster_latest, watch_database, watch_database_latest, watch_latest,
+};
+use ws::handlers::ws;
 
 #[cfg(not(target_env = "msvc"))]
 #[global_allocator]
@@ -88,7 +116,7 @@ pub struct Args {
         long,
         env = "MONGODB_NOAUTH",
         default_value = "false",
-        conflicts_with = "jwks"
+        conflicts_with_all = ["jwks", "issuer", "issuers"]
     )]
     noauth: bool,
 
@@ -97,18 +125,66 @@ pub struct Args {
         short,
         long,
         env = "MONGODB_JWKS_URL",
-        required_unless_present = "noauth"
+        required_unless_present_any = ["noauth", "issuer", "issuers"]
     )]
     jwks: Option<String>,
 
+    /// OIDC issuer, used to discover the JWKS URL and validate the token iss
+    #[arg(short, long, env = "MONGODB_JWKS_ISSUER")]
+    issuer: Option<String>,
+
     /// JWKS Audience
     #[arg(
         short,
         long,
         env = "MONGODB_JWKS_AUDIENCE",
-        required_unless_present = "noauth"
+        required_unless_present_any = ["noauth", "issuers"]
     )]
     audience: Option<String>,
+
+    /// YAML file listing trusted issuers, each with their own JWKS, audiences and claim mapping
+    #[arg(
+        long,
+        env = "MONGODB_JWKS_ISSUERS",
+        conflicts_with_all = ["jwks", "issuer", "audience"]
+    )]
+    issuers: Option<String>,
+
+    /// YAML file listing fields to hide or mask from clients, by db, collection and scope
+    #[arg(long, env = "MONGODB_REDACTION")]
+    redaction: Option<String>,
+
+    /// Seconds a transaction may sit idle before it is aborted
+    #[arg(
+        short,
+        long,
+        env = "MONGODB_TRANSACTION_TIMEOUT",
+        default_value_t = 60
+    )]
+    transaction_timeout: u64,
+
+    /// Seconds a paginated cursor may sit idle before it is dropped
+    #[arg(
+        short,
+        long,
+        env = "MONGODB_CURSOR_TIMEOUT",
+        default_value_t = 300
+    )]
+    cursor_timeout: u64,
+
+    /// Response encodings to offer, comma separated
+    #[arg(
+        long,
+        env = "API_COMPRESSION",
+        value_enum,
+        value_delimiter = ',',
+        default_value = "gzip,br,zstd"
+    )]
+    compression: Vec<compression::Algorithm>,
+
+    /// Minimum response size in bytes before compressing, streams are always compressed
+    #[arg(long, env = "API_COMPRESSION_MIN_SIZE", default_value_t = 1024)]
+    compression_min_size: u16,
 }
 
 #[tokio::main]
@@ -153,7 +229,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         .route("/rs/top", get(rs_top))
         .route("/rs/conn", get(rs_conn))
         .route("/rs/pool", get(rs_pool))
+        .route("/rs/_watch", post(watch_cluster).get(watch_cluster_latest))
         .route("/user/roles", get(token_roles))
+        .route("/transaction/_start", post(start_transaction))
+        .route("/transaction/:id/_commit", post(commit_transaction))
+        .route("/transaction/:id/_abort", post(abort_transaction))
         .route("/db/:db", get(db_colls))
         .route("/db/:db/_stats", get(db_stats))
         .route("/db/:db/_roles", get(get_roles))
@@ -165,10 +245,23 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
             "/db/:db/collection/:coll/_aggregate/explain",
             post(aggregate_explain),
         )
+        .route(
+            "/db/:db/collection/:coll/_aggregate/page",
+            post(aggregate_page),
+        )
+        .route("/db/:db/collection/:coll/_cursor/:id", get(cursor_next))
+        .route("/db/:db/collection/:coll/_bulk_write", post(bulk_write))
         .route("/db/:db/collection/:coll/_count", get(coll_count).post(count))
         .route("/db/:db/collection/:coll/_delete_one", post(delete_one))
         .route("/db/:db/collection/:coll/_delete_many", post(delete_many))
         .route("/db/:db/collection/:coll/_distinct", post(distinct))
+        .route(
+            "/db/:db/collection/:coll/doc/:id",
+            get(document_get)
+                .put(document_replace)
+                .patch(document_update)
+                .delete(document_delete),
+        )
         .route(
             "/db/:db/collection/:coll/_find",
             post(find).get(find_latest_ten),
@@ -178,19 +271,46 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
             post(find_one).get(find_latest_one),
         )
         .route("/db/:db/collection/:coll/_find/explain", post(find_explain))
+        .route("/db/:db/collection/:coll/_find/page", post(find_page))
+        .route(
+            "/db/:db/collection/:coll/_find_one_and_delete",
+            post(find_one_and_delete),
+        )
+        .route(
+            "/db/:db/collection/:coll/_find_one_and_replace",
+            post(find_one_and_replace),
+        )
+        .route(
+            "/db/:db/collection/:coll/_find_one_and_update",
+            post(find_one_and_update),
+        )
         .route("/db/:db/collection/:coll/_indexes", get(indexes))
         .route("/db/:db/collection/:coll/_indexes", post(index_create))
         .route("/db/:db/collection/:coll/_indexes", delete(index_delete))
         .route("/db/:db/collection/:coll/_indexes/stats", get(index_stats))
         .route("/db/:db/collection/:coll/_insert", post(insert_one))
-        .route("/db/:db/collection/:coll/_insert_many", post(insert_many))
+        .route(
+            "/db/:db/collection/:coll/_insert_many",
+            post(insert_many).layer(
+                ServiceBuilder::new()
+                    .layer(HandleErrorLayer::new(compression::decompression_error))
+                    .layer(RequestDecompressionLayer::new()),
+            ),
+        )
+        .route(
+            "/db/:db/collection/:coll/_import",
+            post(import).layer(DefaultBodyLimit::disable()),
+        )
         .route("/db/:db/collection/:coll/_update", post(update_many))
         .route("/db/:db/collection/:coll/_update_one", post(update_one))
+        .route("/db/:db/collection/:coll/_replace_one", post(replace_one))
         .route(
             "/db/:db/collection/:coll/_watch",
             post(watch).get(watch_latest),
         )
         .route("/db/:db/collection/:coll/_stats", get(coll_stats))
+        .route("/db/:db/collection/:coll/_schema", get(schema))
+        .route("/db/:db/_watch", post(watch_database).get(watch_database_latest))
         .route("/", get(root));
 
     // These should NOT be authenticated
@@ -198,15 +318,22 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         .route("/health", get(health))
         .route("/metrics", get(move || ready(recorder_handle.render())));
 
+    // Websockets authenticate themselves, since browsers can't set headers on them
+    let sockets = Router::new()
+        .route("/ws", get(ws))
+        .layer(Extension(auth_jwks.clone()));
+
     let app = Router::new()
         .merge(v1.clone())
         .nest("/api/beta", v1)
         .layer(TraceLayer::new_for_http())
         .route_layer(middleware::from_fn_with_state(auth_jwks.clone(), auth))
         .merge(standard)
+        .merge(sockets)
         .route_layer(middleware::from_fn(track_metrics))
         .fallback(handler_404)
         .layer(DefaultBodyLimit::max(16777216))
+        .layer(compression::layer(&args.compression, args.compression_min_size))
         .layer(Extension(state));
 
     let addr = SocketAddr::from(([0, 0, 0, 0], args.port as u16));
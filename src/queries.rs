@@ -1,4 +1,14 @@
-use serde::{Deserialize, Serialize};
+use bson::Timestamp;
+use core::time::Duration;
+use mongodb::change_stream::event::ResumeToken;
+use mongodb::options::{
+    Acknowledgment, ReadConcern, ReadConcernLevel, ReadPreference, ReadPreferenceOptions,
+    SelectionCriteria, TagSet, WriteConcern,
+};
+use serde::{de, Deserialize, Deserializer, Serialize};
+
+use crate::error::Error as RestError;
+use crate::framing;
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub enum Formats {
@@ -6,6 +16,24 @@ pub enum Formats {
     Json,
     #[serde(rename = "ejson")]
     Ejson,
+    #[serde(rename = "csv")]
+    Csv,
+    #[serde(rename = "bson")]
+    Bson,
+    #[serde(rename = "arrow")]
+    Arrow,
+    #[serde(rename = "parquet")]
+    Parquet,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ReadPreferenceMode {
+    Primary,
+    PrimaryPreferred,
+    Secondary,
+    SecondaryPreferred,
+    Nearest,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -19,10 +47,29 @@ impl Default for Formats {
     }
 }
 
+impl Formats {
+    // Single documents and cursor pages are built as one json value, so csv, bson, arrow and
+    // parquet only apply to streamed finds and aggregations
+    pub fn json_only(format: &Option<Formats>) -> Result<(), RestError> {
+        match format {
+            None | Some(Formats::Json) | Some(Formats::Ejson) => Ok(()),
+            Some(format) => {
+                let name = serde_json::to_value(format).unwrap_or_default();
+                Err(RestError::BadRequest(format!(
+                    "format={} is only supported by _find and _aggregate",
+                    name.as_str().unwrap_or_default()
+                )))
+            }
+        }
+    }
+}
+
 impl Default for QueriesFormat {
     fn default() -> Self {
         QueriesFormat {
             format: Some(Formats::default()),
+            columns: None,
+            schema: None,
         }
     }
 }
@@ -45,9 +92,179 @@ pub struct ExplainFormat {
 #[derive(Clone, Deserialize)]
 pub struct QueriesFormat {
     pub format: Option<Formats>,
+    pub columns: Option<String>,
+    pub schema: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct QueriesWatch {
+    pub format: Option<Formats>,
+    pub resume_after: Option<String>,
+    pub start_after: Option<String>,
+    pub start_at_operation_time: Option<String>,
+}
+
+// Pages are read into memory before they go out, so they are held to this many docs
+pub const MAX_PAGE_SIZE: u64 = 10000;
+
+#[derive(Clone, Deserialize)]
+pub struct QueriesPage {
+    pub format: Option<Formats>,
+    #[serde(default = "default_page_size", deserialize_with = "page_size")]
+    pub page_size: u32,
+}
+
+fn default_page_size() -> u32 {
+    100
+}
+
+// Larger pages are cut down to the maximum, and empty ones are refused
+fn page_size<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+    match u64::deserialize(deserializer)? {
+        0 => Err(de::Error::custom("page_size must be at least 1")),
+        size => Ok(size.min(MAX_PAGE_SIZE) as u32),
+    }
+}
+
+#[derive(Clone, Deserialize)]
+pub struct QueriesImport {
+    pub batch_size: Option<usize>,
 }
 
 #[derive(Deserialize)]
 pub struct QueriesDelete {
     pub name: String,
 }
+
+// Concerns from the query string win over those set in the body's options, which are a
+// different type for every operation, so this is a macro rather than a function
+macro_rules! concerns {
+    ($options:ident, $concern:expr, read) => {
+        $options.read_concern = $concern.read_concern().or($options.read_concern);
+        $options.selection_criteria = $concern.selection_criteria().or($options.selection_criteria);
+    };
+    ($options:ident, $concern:expr, write) => {
+        $options.write_concern = $concern.write_concern().or($options.write_concern);
+    };
+    ($options:ident, $concern:expr, read, write) => {
+        concerns!($options, $concern, read);
+        concerns!($options, $concern, write);
+    };
+}
+pub(crate) use concerns;
+
+// Read and write concern knobs shared by every find, aggregate, count, insert, update and delete
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct QueriesConcern {
+    pub w: Option<Acknowledgment>,
+    pub n: Option<u32>,
+    pub w_timeout: Option<u64>,
+    pub journal: Option<bool>,
+    pub read_concern: Option<ReadConcernLevel>,
+    pub read_preference: Option<ReadPreferenceMode>,
+    pub read_preference_tags: Option<String>,
+    pub max_staleness: Option<u64>,
+}
+
+impl QueriesConcern {
+    pub fn write_concern(&self) -> Option<WriteConcern> {
+        if self.w.is_none() && self.n.is_none() && self.w_timeout.is_none() && self.journal.is_none() {
+            return None;
+        }
+
+        let w_concern = if let Some(w) = self.w.clone() {
+            Some(w)
+        } else {
+            self.n.map(|n| n.into())
+        };
+
+        Some(
+            WriteConcern::builder()
+                .w(w_concern)
+                .w_timeout(self.w_timeout.map(Duration::from_millis))
+                .journal(self.journal)
+                .build(),
+        )
+    }
+
+    pub fn read_concern(&self) -> Option<ReadConcern> {
+        self.read_concern.clone().map(|level| level.into())
+    }
+
+    pub fn selection_criteria(&self) -> Option<SelectionCriteria> {
+        let mode = self.read_preference.as_ref()?;
+
+        let options = ReadPreferenceOptions::builder()
+            .tag_sets(self.tag_sets())
+            .max_staleness(self.max_staleness.map(Duration::from_secs))
+            .build();
+
+        let read_preference = match mode {
+            ReadPreferenceMode::Primary => ReadPreference::Primary,
+            ReadPreferenceMode::PrimaryPreferred => ReadPreference::PrimaryPreferred { options },
+            ReadPreferenceMode::Secondary => ReadPreference::Secondary { options },
+            ReadPreferenceMode::SecondaryPreferred => {
+                ReadPreference::SecondaryPreferred { options }
+            }
+            ReadPreferenceMode::Nearest => ReadPreference::Nearest { options },
+        };
+
+        Some(SelectionCriteria::ReadPreference(read_preference))
+    }
+
+    // Tag sets are separated by ';' and tried in order, eg: dc:east,use:analytics;dc:west;
+    // A trailing empty set falls back to any eligible member
+    fn tag_sets(&self) -> Option<Vec<TagSet>> {
+        let tags = self.read_preference_tags.as_ref()?;
+
+        Some(
+            tags.split(';')
+                .map(|set| {
+                    set.split(',')
+                        .filter_map(|tag| tag.split_once(':'))
+                        .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+                        .collect()
+                })
+                .collect(),
+        )
+    }
+}
+
+impl QueriesWatch {
+    pub fn resume_after(&self) -> Result<Option<ResumeToken>, RestError> {
+        Self::token("resume_after", &self.resume_after)
+    }
+
+    pub fn start_after(&self) -> Result<Option<ResumeToken>, RestError> {
+        Self::token("start_after", &self.start_after)
+    }
+
+    // Operation times are seconds since the epoch, optionally followed by the increment, eg: 1700000000:1
+    pub fn start_at_operation_time(&self) -> Result<Option<Timestamp>, RestError> {
+        let time = match self.start_at_operation_time {
+            Some(ref time) => time,
+            None => return Ok(None),
+        };
+
+        let (seconds, increment) = time.split_once(':').unwrap_or((time, "0"));
+        match (seconds.parse(), increment.parse()) {
+            (Ok(time), Ok(increment)) => Ok(Some(Timestamp { time, increment })),
+            _ => Err(RestError::BadRequest(format!(
+                "Invalid start_at_operation_time: {}",
+                time
+            ))),
+        }
+    }
+
+    fn token(name: &str, token: &Option<String>) -> Result<Option<ResumeToken>, RestError> {
+        let token = match token {
+            Some(token) => token,
+            None => return Ok(None),
+        };
+
+        match framing::resume_token(token) {
+            Some(token) => Ok(Some(token)),
+            None => Err(RestError::BadRequest(format!("Invalid {}: {}", name, token))),
+        }
+    }
+}
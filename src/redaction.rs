@@ -0,0 +1,851 @@
+use bson::{doc, Bson, Document};
+use futures::{Stream, StreamExt};
+use mongodb::change_stream::event::ChangeStreamEvent;
+use mongodb::options::UpdateModifications;
+use serde::Deserialize;
+
+use crate::error::Error as RestError;
+
+type MyResult<T> = std::result::Result<T, RestError>;
+
+// What masked fields read as
+const MASK: &str = "****";
+
+// Where documents sit in a change event
+const CHANGE_FIELDS: [&str; 3] = [
+    "fullDocument.",
+    "fullDocumentBeforeChange.",
+    "updateDescription.updatedFields.",
+];
+
+// Fields some clients may not see, as listed in the --redaction file, eg:
+//
+// - db: sales
+//   coll: customers
+//   fields: [ssn, card.number]
+//   except: [mongodb.role.sales.customers:pii]
+// - db: sales
+//   fields: [email]
+//   action: mask
+//   scopes: [mongodb.role.sales:read]
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    pub db: String,
+    // Every collection in the db when missing
+    pub coll: Option<String>,
+    pub fields: Vec<String>,
+    #[serde(default)]
+    pub action: Action,
+    // Only clients holding one of these scopes are redacted, or every client when empty
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    // Clients holding one of these scopes see the fields as they are
+    #[serde(default)]
+    pub except: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+pub enum Action {
+    #[default]
+    #[serde(rename = "hide")]
+    Hide,
+    #[serde(rename = "mask")]
+    Mask,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Policy {
+    rules: Vec<Rule>,
+}
+
+// The rules that apply to a single client
+#[derive(Debug, Clone, Default)]
+pub struct Redactions {
+    rules: Vec<Rule>,
+}
+
+// The fields redacted from a single collection, or from every collection a pipeline can see
+#[derive(Debug, Clone, Default)]
+pub struct Redaction {
+    hidden: Vec<String>,
+    masked: Vec<String>,
+}
+
+impl Policy {
+    pub fn load(path: Option<&str>) -> MyResult<Self> {
+        let path = match path {
+            Some(path) => path,
+            None => return Ok(Policy::default()),
+        };
+
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| RestError::Config(format!("Unable to read {}: {}", path, e)))?;
+        let rules = serde_yaml::from_str::<Vec<Rule>>(&contents)
+            .map_err(|e| RestError::Config(format!("Unable to parse {}: {}", path, e)))?;
+
+        log::debug!("Loaded {} redaction rules", rules.len());
+        Ok(Policy { rules })
+    }
+
+    pub fn redactions(&self, scopes: &[String]) -> Redactions {
+        let holds = |wanted: &Vec<String>| wanted.iter().any(|scope| scopes.contains(scope));
+
+        Redactions {
+            rules: self
+                .rules
+                .iter()
+                .filter(|rule| rule.scopes.is_empty() || holds(&rule.scopes))
+                .filter(|rule| !holds(&rule.except))
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+impl Redactions {
+    pub fn collection(&self, db: &str, coll: &str) -> Redaction {
+        self.matching(|rule| rule.db == db && (rule.coll.is_none() || rule.coll.as_deref() == Some(coll)))
+    }
+
+    // Everything redacted in a database, or in every database, for change streams that span collections
+    pub fn database(&self, db: Option<&str>) -> Redaction {
+        self.matching(|rule| db.is_none() || db == Some(rule.db.as_str()))
+    }
+
+    fn matching<F: Fn(&Rule) -> bool>(&self, filter: F) -> Redaction {
+        let mut redaction = Redaction::default();
+        for rule in self.rules.iter().filter(|rule| filter(rule)) {
+            match rule.action {
+                Action::Hide => redaction.hidden.extend(rule.fields.iter().cloned()),
+                Action::Mask => redaction.masked.extend(rule.fields.iter().cloned()),
+            }
+        }
+        redaction.normalize();
+        redaction
+    }
+
+    // Redacts the collection before the pipeline sees it, so no stage can copy the fields
+    // elsewhere, along with any collection the pipeline pulls in
+    pub fn pipeline(&self, db: &str, coll: &str, pipeline: Vec<Document>) -> MyResult<Vec<Document>> {
+        let mut stages = self.collection(db, coll).stages();
+        stages.extend(self.joins(db, pipeline)?);
+        Ok(stages)
+    }
+
+    fn joins(&self, db: &str, pipeline: Vec<Document>) -> MyResult<Vec<Document>> {
+        pipeline
+            .into_iter()
+            .map(|stage| self.join(db, stage))
+            .collect()
+    }
+
+    fn join(&self, db: &str, mut stage: Document) -> MyResult<Document> {
+        for (name, spec) in stage.iter_mut() {
+            match (name.as_str(), spec) {
+                ("$lookup", Bson::Document(spec)) => self.nested(db, spec, "from")?,
+                ("$unionWith", Bson::Document(spec)) => self.nested(db, spec, "coll")?,
+                ("$unionWith", spec @ Bson::String(_)) => {
+                    let mut union = doc! {"coll": spec.clone()};
+                    self.nested(db, &mut union, "coll")?;
+                    *spec = Bson::Document(union);
+                }
+                ("$facet", Bson::Document(spec)) => {
+                    for (_, pipeline) in spec.iter_mut() {
+                        *pipeline = Bson::from(self.joins(db, documents(pipeline))?);
+                    }
+                }
+                // $graphLookup has no pipeline to redact the other collection with
+                ("$graphLookup", Bson::Document(spec)) => {
+                    let from = spec.get_str("from").unwrap_or_default();
+                    if !self.collection(db, from).is_empty() {
+                        return Err(RestError::BadRequest(format!(
+                            "$graphLookup can't be used on {}, as it has redacted fields",
+                            from
+                        )));
+                    };
+                }
+                _ => (),
+            }
+        }
+        Ok(stage)
+    }
+
+    // $lookup and $unionWith take a pipeline that runs against the other collection
+    fn nested(&self, db: &str, spec: &mut Document, from: &str) -> MyResult<()> {
+        let pipeline = spec.get("pipeline").map(documents).unwrap_or_default();
+        let pipeline = match spec.get_str(from) {
+            Ok(from) => self.pipeline(db, from, pipeline)?,
+            // $lookup on $documents, which has nothing to redact
+            Err(_) => self.joins(db, pipeline)?,
+        };
+
+        if !pipeline.is_empty() || spec.contains_key("pipeline") {
+            spec.insert("pipeline", pipeline);
+        };
+        Ok(())
+    }
+
+    // Change streams span namespaces, so each event is redacted for its own
+    pub fn event(&self, event: &mut ChangeStreamEvent<Document>) {
+        let redaction = match event.ns {
+            Some(ref ns) => self.collection(&ns.db, ns.coll.as_deref().unwrap_or_default()),
+            None => return,
+        };
+        redaction.event(event);
+    }
+}
+
+impl Redaction {
+    pub fn is_empty(&self) -> bool {
+        self.hidden.is_empty() && self.masked.is_empty()
+    }
+
+    // Overlapping paths make the server reject projections and $unset, so only the outermost is kept
+    fn normalize(&mut self) {
+        self.hidden.sort();
+        self.hidden.dedup();
+        let hidden = self.hidden.clone();
+        self.hidden
+            .retain(|field| !hidden.iter().any(|outer| field != outer && covers(outer, field)));
+
+        self.masked.sort();
+        self.masked.dedup();
+        let masked = self.masked.clone();
+        self.masked.retain(|field| {
+            !hidden.iter().any(|outer| covers(outer, field))
+                && !masked.iter().any(|outer| field != outer && covers(outer, field))
+        });
+    }
+
+    // Hidden fields are excluded by the server where the projection allows it, apply takes care of the rest
+    pub fn projection(&self, projection: Option<Document>) -> MyResult<Option<Document>> {
+        if self.is_empty() {
+            return Ok(projection);
+        }
+
+        let projection = projection.unwrap_or_default();
+        for (_, value) in projection.iter() {
+            self.check(value, &[""])?;
+        }
+
+        let inclusion = projection
+            .iter()
+            .any(|(key, value)| key != "_id" && included(value));
+
+        if inclusion {
+            let kept: Document = projection
+                .iter()
+                .filter(|(key, _)| !self.hidden.iter().any(|field| covers(field, key)))
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect();
+
+            // Dropping every included field would turn this into an exclusion projection
+            let left = kept.iter().any(|(key, value)| key != "_id" && included(value));
+            return Ok(Some(if left { kept } else { projection }));
+        }
+
+        let mut projection = projection;
+        for field in &self.hidden {
+            if projection.keys().any(|key| covers(key, field)) {
+                continue;
+            }
+            let covered: Vec<String> = projection
+                .keys()
+                .filter(|key| covers(field, key))
+                .cloned()
+                .collect();
+            for key in covered {
+                projection.remove(&key);
+            }
+            projection.insert(field.clone(), 0);
+        }
+        Ok(Some(projection))
+    }
+
+    // Stages that redact documents before the rest of a pipeline sees them
+    pub fn stages(&self) -> Vec<Document> {
+        let mut stages = Vec::new();
+        if !self.hidden.is_empty() {
+            stages.push(doc! {"$unset": self.hidden.clone()});
+        };
+
+        if !self.masked.is_empty() {
+            let masked: Document = self
+                .masked
+                .iter()
+                .map(|field| {
+                    let value = doc! {"$cond": [
+                        {"$eq": [{"$type": format!("${}", field)}, "missing"]},
+                        "$$REMOVE",
+                        MASK,
+                    ]};
+                    (field.clone(), Bson::Document(value))
+                })
+                .collect();
+            stages.push(doc! {"$set": masked});
+        };
+        stages
+    }
+
+    // Expressions that reference a redacted field, or a document holding one, would copy it
+    // past the redaction, so they are refused. Paths are checked under each of the prefixes.
+    pub fn check(&self, value: &Bson, prefixes: &[&str]) -> MyResult<()> {
+        match value {
+            Bson::String(value) => {
+                let path = match reference(value) {
+                    Some(path) => path,
+                    None => return Ok(()),
+                };
+
+                for field in self.hidden.iter().chain(self.masked.iter()) {
+                    for prefix in prefixes {
+                        let field = format!("{}{}", prefix, field);
+                        if path.is_empty() || covers(path, &field) || covers(&field, path) {
+                            return Err(RestError::BadRequest(format!(
+                                "{} can't be referenced, as it holds redacted fields",
+                                value
+                            )));
+                        };
+                    }
+                }
+                Ok(())
+            }
+            Bson::Document(doc) => doc.iter().try_for_each(|(_, value)| self.check(value, prefixes)),
+            Bson::Array(items) => items.iter().try_for_each(|value| self.check(value, prefixes)),
+            _ => Ok(()),
+        }
+    }
+
+    // Change stream pipelines see whole events, so they can't reference what is redacted from them
+    pub fn watch(&self, pipeline: &[Document]) -> MyResult<()> {
+        for stage in pipeline {
+            for (_, value) in stage.iter() {
+                self.check(value, &CHANGE_FIELDS)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Pipeline updates can copy a redacted field into one the client is able to read back, and
+    // update documents name the fields they write, which $rename would move somewhere readable
+    pub fn update(&self, update: &UpdateModifications) -> MyResult<()> {
+        match update {
+            UpdateModifications::Pipeline(pipeline) => {
+                for stage in pipeline {
+                    for (name, value) in stage.iter() {
+                        match (name.as_str(), value) {
+                            ("$set" | "$addFields", Bson::Document(fields)) => {
+                                fields.keys().try_for_each(|key| self.field(key))?
+                            }
+                            ("$unset", Bson::String(key)) => self.field(key)?,
+                            ("$unset", Bson::Array(keys)) => keys
+                                .iter()
+                                .filter_map(|key| key.as_str())
+                                .try_for_each(|key| self.field(key))?,
+                            _ => (),
+                        };
+                        self.check(value, &[""])?;
+                    }
+                }
+                Ok(())
+            }
+            UpdateModifications::Document(update) => self.modifiers(update),
+            // The driver marks the enum non exhaustive, there are no other variants
+            _ => Ok(()),
+        }
+    }
+
+    // Every update operator is keyed by the fields it writes, $rename also names where they go
+    pub fn modifiers(&self, update: &Document) -> MyResult<()> {
+        for (operator, spec) in update.iter() {
+            let spec = match spec {
+                Bson::Document(spec) => spec,
+                _ => continue,
+            };
+
+            for (key, value) in spec.iter() {
+                self.field(key)?;
+                if let ("$rename", Bson::String(target)) = (operator.as_str(), value) {
+                    self.field(target)?;
+                };
+            }
+        }
+        Ok(())
+    }
+
+    // Replacements would overwrite redacted fields the client can't see
+    pub fn replacement(&self, replacement: &Document) -> MyResult<()> {
+        replacement
+            .keys()
+            .filter(|key| key.as_str() != "_id")
+            .try_for_each(|key| self.field(key))
+    }
+
+    // Filters on a redacted field let a client work out its value from which documents match,
+    // such as with a $regex one character at a time, so they are refused along with sorts
+    pub fn filter(&self, filter: &Document) -> MyResult<()> {
+        self.conditions(filter, "")
+    }
+
+    fn conditions(&self, filter: &Document, prefix: &str) -> MyResult<()> {
+        if self.is_empty() {
+            return Ok(());
+        }
+
+        for (key, value) in filter.iter() {
+            match key.as_str() {
+                "$and" | "$or" | "$nor" => {
+                    for clause in documents(value) {
+                        self.conditions(&clause, prefix)?;
+                    }
+                }
+                "$expr" => self.check(value, &[""])?,
+                // Both see the whole document
+                "$where" | "$jsonSchema" => {
+                    return Err(RestError::BadRequest(format!(
+                        "{} can't be used on a collection with redacted fields",
+                        key
+                    )))
+                }
+                // Operators inside $elemMatch compare the array's items as a whole
+                key if key.starts_with('$') => {
+                    if !prefix.is_empty() {
+                        self.field(prefix.trim_end_matches('.'))?;
+                    };
+                }
+                key => {
+                    let path = format!("{}{}", prefix, key);
+                    // Conditions in $elemMatch are on the fields of the array's items
+                    match value.as_document().map(|ops| (ops.len(), ops.get("$elemMatch"))) {
+                        Some((1, Some(Bson::Document(inner)))) => {
+                            self.conditions(inner, &format!("{}.", path))?
+                        }
+                        _ => self.field(&path)?,
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Sorts, and the min and max index bounds, order documents by the fields they name
+    pub fn sort(&self, sort: &Option<Document>) -> MyResult<()> {
+        match sort {
+            Some(sort) => sort.keys().try_for_each(|key| self.field(key)),
+            None => Ok(()),
+        }
+    }
+
+    // A path is refused when it is redacted, holds a redacted field or sits under one
+    pub fn field(&self, path: &str) -> MyResult<()> {
+        let path = unpositioned(path);
+        for field in self.hidden.iter().chain(self.masked.iter()) {
+            if covers(field, &path) || covers(&path, field) {
+                return Err(RestError::BadRequest(format!(
+                    "{} can't be used, as it holds redacted fields",
+                    path
+                )));
+            };
+        }
+        Ok(())
+    }
+
+    pub fn cursor<S>(self, docs: S) -> impl Stream<Item = mongodb::error::Result<Document>> + Send + Unpin
+    where
+        S: Stream<Item = mongodb::error::Result<Document>> + Send + Unpin,
+    {
+        docs.map(move |doc| {
+            doc.map(|mut doc| {
+                self.apply(&mut doc);
+                doc
+            })
+        })
+    }
+
+    pub fn apply(&self, doc: &mut Document) {
+        for field in &self.hidden {
+            visit(doc, field, &|doc, key| {
+                doc.remove(key);
+            });
+        }
+
+        for field in &self.masked {
+            visit(doc, field, &|doc, key| {
+                if doc.contains_key(key) {
+                    doc.insert(key, MASK);
+                };
+            });
+        }
+    }
+
+    pub fn event(&self, event: &mut ChangeStreamEvent<Document>) {
+        if self.is_empty() {
+            return;
+        }
+
+        if let Some(ref mut doc) = event.full_document {
+            self.apply(doc);
+        };
+
+        if let Some(ref mut doc) = event.full_document_before_change {
+            self.apply(doc);
+        };
+
+        // Updated fields are keyed by their dotted path, such as address.street or items.0.card
+        if let Some(ref mut update) = event.update_description {
+            let updated = std::mem::take(&mut update.updated_fields);
+            for (key, mut value) in updated {
+                let path = unindexed(&key);
+                if self.hidden.iter().any(|field| covers(field, &path)) {
+                    continue;
+                }
+
+                if self.masked.iter().any(|field| covers(field, &path)) {
+                    value = Bson::from(MASK);
+                } else if let Bson::Document(ref mut doc) = value {
+                    let inner = Redaction {
+                        hidden: self.hidden.iter().filter_map(|field| under(&path, field)).collect(),
+                        masked: self.masked.iter().filter_map(|field| under(&path, field)).collect(),
+                    };
+                    inner.apply(doc);
+                };
+
+                update.updated_fields.insert(key, value);
+            }
+        };
+    }
+}
+
+// Whether outer is path, or one of its parents
+fn covers(outer: &str, path: &str) -> bool {
+    path == outer || path.starts_with(&format!("{}.", outer))
+}
+
+// The rest of field, when it sits under path
+fn under(path: &str, field: &str) -> Option<String> {
+    field
+        .strip_prefix(&format!("{}.", path))
+        .map(|rest| rest.to_string())
+}
+
+// The document path an expression string refers to, $$ROOT and $$CURRENT being the whole document
+fn reference(value: &str) -> Option<&str> {
+    for root in ["$$ROOT", "$$CURRENT"] {
+        if let Some(rest) = value.strip_prefix(root) {
+            return Some(rest.trim_start_matches('.'));
+        }
+    }
+
+    match value.starts_with("$$") {
+        true => None,
+        false => value.strip_prefix('$'),
+    }
+}
+
+// Array indexes don't change which field a path names
+fn unindexed(path: &str) -> String {
+    path.split('.')
+        .filter(|part| part.parse::<usize>().is_err())
+        .collect::<Vec<&str>>()
+        .join(".")
+}
+
+// Update paths can also hold the $, $[] and $[name] positional operators
+fn unpositioned(path: &str) -> String {
+    path.split('.')
+        .filter(|part| part.parse::<usize>().is_err() && !part.starts_with('$'))
+        .collect::<Vec<&str>>()
+        .join(".")
+}
+
+fn included(value: &Bson) -> bool {
+    !matches!(
+        value,
+        Bson::Int32(0) | Bson::Int64(0) | Bson::Boolean(false)
+    ) && !matches!(value, Bson::Double(d) if *d == 0.0)
+}
+
+// Runs f on the document holding the last part of path, through any arrays along the way
+fn visit(doc: &mut Document, path: &str, f: &dyn Fn(&mut Document, &str)) {
+    match path.split_once('.') {
+        None => f(doc, path),
+        Some((head, rest)) => match doc.get_mut(head) {
+            Some(Bson::Document(inner)) => visit(inner, rest, f),
+            Some(Bson::Array(items)) => {
+                for item in items {
+                    if let Bson::Document(inner) = item {
+                        visit(inner, rest, f);
+                    };
+                }
+            }
+            _ => (),
+        },
+    }
+}
+
+fn documents(pipeline: &Bson) -> Vec<Document> {
+    match pipeline {
+        Bson::Array(stages) => stages
+            .iter()
+            .filter_map(|stage| stage.as_document().cloned())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(coll: Option<&str>, fields: &[&str], action: Action) -> Rule {
+        Rule {
+            db: "sales".to_string(),
+            coll: coll.map(|coll| coll.to_string()),
+            fields: fields.iter().map(|field| field.to_string()).collect(),
+            action,
+            scopes: Vec::new(),
+            except: Vec::new(),
+        }
+    }
+
+    // ssn and card are hidden in sales.customers, email is masked across sales
+    fn redactions() -> Redactions {
+        let policy = Policy {
+            rules: vec![
+                rule(Some("customers"), &["ssn", "card.number", "card"], Action::Hide),
+                rule(None, &["email", "card.cvv"], Action::Mask),
+            ],
+        };
+        policy.redactions(&[])
+    }
+
+    #[test]
+    fn normalize_keeps_outermost_fields() {
+        let redaction = redactions().collection("sales", "customers");
+        assert_eq!(redaction.hidden, vec!["card", "ssn"]);
+        assert_eq!(redaction.masked, vec!["email"]);
+    }
+
+    #[test]
+    fn scopes_and_except_pick_rules() {
+        let mut scoped = rule(None, &["ssn"], Action::Hide);
+        scoped.scopes = vec!["mongodb.role.sales:read".to_string()];
+        scoped.except = vec!["mongodb.role.sales:pii".to_string()];
+        let policy = Policy { rules: vec![scoped] };
+
+        let reader = vec!["mongodb.role.sales:read".to_string()];
+        let trusted = vec![reader[0].clone(), "mongodb.role.sales:pii".to_string()];
+        assert!(!policy.redactions(&reader).collection("sales", "orders").is_empty());
+        assert!(policy.redactions(&trusted).collection("sales", "orders").is_empty());
+        assert!(policy.redactions(&[]).collection("sales", "orders").is_empty());
+    }
+
+    #[test]
+    fn projection_excludes_hidden_fields() {
+        let redaction = redactions().collection("sales", "customers");
+
+        let projection = redaction.projection(None).unwrap().unwrap();
+        assert_eq!(projection, doc! {"card": 0, "ssn": 0});
+
+        // Excluding a field under a hidden one would collide with the hidden path
+        let projection = redaction.projection(Some(doc! {"card.brand": 0})).unwrap().unwrap();
+        assert_eq!(projection, doc! {"card": 0, "ssn": 0});
+    }
+
+    #[test]
+    fn projection_drops_hidden_inclusions() {
+        let redaction = redactions().collection("sales", "customers");
+
+        let projection = redaction
+            .projection(Some(doc! {"name": 1, "ssn": 1, "card.brand": 1}))
+            .unwrap()
+            .unwrap();
+        assert_eq!(projection, doc! {"name": 1});
+
+        // Only hidden fields included, which apply removes after the fact
+        let projection = redaction.projection(Some(doc! {"ssn": 1})).unwrap().unwrap();
+        assert_eq!(projection, doc! {"ssn": 1});
+    }
+
+    #[test]
+    fn projection_refuses_references() {
+        let redaction = redactions().collection("sales", "customers");
+        assert!(redaction.projection(Some(doc! {"copy": "$ssn"})).is_err());
+        assert!(redaction.projection(Some(doc! {"copy": "$card.number"})).is_err());
+        assert!(redaction.projection(Some(doc! {"copy": "$$ROOT"})).is_err());
+        assert!(redaction.projection(Some(doc! {"copy": {"$toUpper": "$email"}})).is_err());
+        assert!(redaction.projection(Some(doc! {"copy": "$name"})).is_ok());
+    }
+
+    #[test]
+    fn update_refuses_pipeline_references() {
+        let redaction = redactions().collection("sales", "customers");
+
+        let pipeline = UpdateModifications::Pipeline(vec![doc! {"$set": {"copy": "$ssn"}}]);
+        assert!(redaction.update(&pipeline).is_err());
+
+        let pipeline = UpdateModifications::Pipeline(vec![doc! {"$set": {"copy": "$name"}}]);
+        assert!(redaction.update(&pipeline).is_ok());
+
+        let pipeline = UpdateModifications::Pipeline(vec![doc! {"$unset": ["name", "card.brand"]}]);
+        assert!(redaction.update(&pipeline).is_err());
+
+        // Values in an update document are literals
+        let update = UpdateModifications::Document(doc! {"$set": {"copy": "$ssn"}});
+        assert!(redaction.update(&update).is_ok());
+    }
+
+    #[test]
+    fn update_refuses_redacted_modifiers() {
+        let redaction = redactions().collection("sales", "customers");
+        let update = |update: Document| redaction.update(&UpdateModifications::Document(update));
+
+        assert!(update(doc! {"$rename": {"ssn": "public"}}).is_err());
+        assert!(update(doc! {"$rename": {"public": "card.number"}}).is_err());
+        assert!(update(doc! {"$rename": {"name": "full_name"}}).is_ok());
+
+        assert!(update(doc! {"$set": {"card": {}}}).is_err());
+        assert!(update(doc! {"$unset": {"email": ""}}).is_err());
+        assert!(update(doc! {"$set": {"contacts.$[].email": "a@example.com"}}).is_ok());
+        assert!(update(doc! {"$inc": {"visits": 1}, "$set": {"name": "a"}}).is_ok());
+
+        assert!(redaction.replacement(&doc! {"_id": 1, "name": "a"}).is_ok());
+        assert!(redaction.replacement(&doc! {"_id": 1, "ssn": "123"}).is_err());
+    }
+
+    #[test]
+    fn filter_and_sort_refuse_redacted_fields() {
+        let redaction = redactions().collection("sales", "customers");
+
+        assert!(redaction.filter(&doc! {"ssn": {"$regex": "^1"}}).is_err());
+        assert!(redaction.filter(&doc! {"card.number": "4111"}).is_err());
+        assert!(redaction.filter(&doc! {"$or": [{"name": "a"}, {"email": "a@example.com"}]}).is_err());
+        assert!(redaction.filter(&doc! {"$expr": {"$eq": ["$ssn", "123"]}}).is_err());
+        assert!(redaction.filter(&doc! {"$where": "this.name == 'a'"}).is_err());
+        assert!(redaction.filter(&doc! {"name": "a", "$and": [{"visits": {"$gt": 1}}]}).is_ok());
+
+        // Conditions inside $elemMatch name fields of the matched array's items
+        let redactions = Policy {
+            rules: vec![rule(None, &["items.price"], Action::Hide)],
+        }
+        .redactions(&[]);
+        let redaction = redactions.collection("sales", "orders");
+        assert!(redaction.filter(&doc! {"items": {"$elemMatch": {"price": {"$gt": 10}}}}).is_err());
+        assert!(redaction.filter(&doc! {"items": {"$elemMatch": {"sku": "a"}}}).is_ok());
+        assert!(redaction.filter(&doc! {"items": {"$elemMatch": {"$eq": {"sku": "a"}}}}).is_err());
+        assert!(redaction.filter(&doc! {"items": {"$size": 2}}).is_err());
+        assert!(redaction.filter(&doc! {"total": {"$gt": 10}}).is_ok());
+
+        assert!(redaction.sort(&Some(doc! {"items.0.price": 1})).is_err());
+        assert!(redaction.sort(&Some(doc! {"total": -1})).is_ok());
+        assert!(redaction.sort(&None).is_ok());
+    }
+
+    #[test]
+    fn apply_hides_and_masks() {
+        let redaction = redactions().collection("sales", "customers");
+        let mut doc = doc! {
+            "name": "a",
+            "ssn": "123",
+            "card": {"number": "4111"},
+            "email": "a@example.com",
+        };
+        redaction.apply(&mut doc);
+        assert_eq!(doc, doc! {"name": "a", "email": MASK});
+    }
+
+    #[test]
+    fn event_redacts_documents_and_updated_fields() {
+        let mut event: ChangeStreamEvent<Document> = bson::from_document(doc! {
+            "_id": {"_data": "token"},
+            "operationType": "update",
+            "ns": {"db": "sales", "coll": "customers"},
+            "fullDocument": {"name": "a", "ssn": "123", "email": "a@example.com"},
+            "updateDescription": {
+                "updatedFields": {
+                    "ssn": "456",
+                    "card.number": "4111",
+                    "email": "b@example.com",
+                    "contacts.0.email": "c@example.com",
+                    "name": "b",
+                },
+                "removedFields": [],
+            },
+        })
+        .unwrap();
+        redactions().event(&mut event);
+
+        assert_eq!(event.full_document.unwrap(), doc! {"name": "a", "email": MASK});
+        assert_eq!(
+            event.update_description.unwrap().updated_fields,
+            doc! {"email": MASK, "contacts.0.email": "c@example.com", "name": "b"}
+        );
+    }
+
+    #[test]
+    fn join_redacts_lookup_and_union() {
+        let redactions = redactions();
+        let pipeline = redactions
+            .pipeline(
+                "sales",
+                "orders",
+                vec![
+                    doc! {"$lookup": {"from": "customers", "localField": "customer", "foreignField": "_id", "as": "customer"}},
+                    doc! {"$unionWith": "customers"},
+                ],
+            )
+            .unwrap();
+
+        let orders = redactions.collection("sales", "orders").stages();
+        let customers = redactions.collection("sales", "customers").stages();
+        assert_eq!(orders.len(), 1);
+        assert_eq!(customers.len(), 2);
+
+        assert_eq!(pipeline[0], orders[0]);
+        assert_eq!(
+            pipeline[1],
+            doc! {"$lookup": {"from": "customers", "localField": "customer", "foreignField": "_id", "as": "customer", "pipeline": customers.clone()}}
+        );
+        assert_eq!(
+            pipeline[2],
+            doc! {"$unionWith": {"coll": "customers", "pipeline": customers}}
+        );
+    }
+
+    #[test]
+    fn join_recurses_into_facets() {
+        let pipeline = redactions()
+            .pipeline(
+                "sales",
+                "products",
+                vec![doc! {"$facet": {"buyers": [{"$unionWith": {"coll": "customers", "pipeline": [{"$match": {}}]}}]}}],
+            )
+            .unwrap();
+
+        let union = pipeline[1]
+            .get_document("$facet")
+            .unwrap()
+            .get_array("buyers")
+            .unwrap()[0]
+            .as_document()
+            .unwrap()
+            .get_document("$unionWith")
+            .unwrap();
+        let stages = union.get_array("pipeline").unwrap();
+        assert_eq!(stages.len(), 3);
+        assert_eq!(stages[0], Bson::from(doc! {"$unset": ["card", "ssn"]}));
+        assert_eq!(stages[2], Bson::from(doc! {"$match": {}}));
+    }
+
+    #[test]
+    fn join_refuses_graph_lookup_on_redacted_collections() {
+        let redactions = redactions();
+        let graph = |from: &str| {
+            vec![doc! {"$graphLookup": {"from": from, "startWith": "$manager", "connectFromField": "manager", "connectToField": "_id", "as": "chain"}}]
+        };
+        assert!(redactions.pipeline("sales", "orders", graph("customers")).is_err());
+        assert!(redactions.pipeline("sales", "orders", graph("orders")).is_err());
+        assert!(redactions.pipeline("other", "orders", graph("orders")).is_ok());
+    }
+}
@@ -1,17 +1,16 @@
 use axum::{
-    body::Bytes,
-    body::StreamBody,
     extract::{Path, Query},
+    response::IntoResponse,
     Extension, Json,
 };
 use bson::doc;
-use futures::Stream;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 
 use crate::error::Error as RestError;
 use crate::find::structs::Find;
-use crate::queries::QueriesFormat;
+use crate::framing::Framing;
+use crate::queries::{QueriesConcern, QueriesFormat};
 use crate::scopes::AuthorizeScope;
 use crate::State;
 
@@ -55,9 +54,10 @@ pub async fn get_roles(
     Extension(scopes): Extension<AuthorizeScope>,
     Path(db): Path<String>,
     queries: Query<QueriesFormat>,
-) -> Result<StreamBody<impl Stream<Item = Result<Bytes, RestError>>>, RestError> {
+    framing: Framing,
+) -> Result<impl IntoResponse, RestError> {
     // Validate that the client has access to this database
-    scopes.dbadmin(&db)?;
+    scopes.dbadmin(&db, None)?;
 
     log::info!("{{\"fn\": \"get_roles\", \"db\": \"{}\"}}", &db);
     let payload = Find {
@@ -65,7 +65,7 @@ pub async fn get_roles(
         options: None,
     };
 
-    state.db.find(&db, &"system.roles", payload, queries).await
+    state.db.find(&db, &"system.roles", payload, queries, framing, &QueriesConcern::default(), &scopes).await
 }
 
 pub async fn create_role(
@@ -76,7 +76,7 @@ pub async fn create_role(
     Json(payload): Json<CreateRole>,
 ) -> Result<Json<Value>, RestError> {
     // Validate that the client has access to this database
-    scopes.dbadmin(&db)?;
+    scopes.dbadmin(&db, None)?;
 
     log::info!("{{\"fn\": \"create_role\", \"db\": \"{}\"}}", &db);
     Ok(Json(json!(state.db.run_command(&db, payload, true).await?)))
@@ -88,7 +88,7 @@ pub async fn drop_role(
     Path((db, role)): Path<(String, String)>,
 ) -> Result<Json<Value>, RestError> {
     // Validate that the client has access to this database
-    scopes.dbadmin(&db)?;
+    scopes.dbadmin(&db, None)?;
 
     log::info!("{{\"fn\": \"drop_role\", \"db\": \"{}\", \"role\": \"{}\"}}", &db, &role);
 
@@ -102,9 +102,10 @@ pub async fn get_role(
     Extension(scopes): Extension<AuthorizeScope>,
     Path((db, name)): Path<(String, String)>,
     queries: Query<QueriesFormat>,
-) -> Result<StreamBody<impl Stream<Item = Result<Bytes, RestError>>>, RestError> {
+    framing: Framing,
+) -> Result<impl IntoResponse, RestError> {
     // Validate that the client has access to this database
-    scopes.dbadmin(&db)?;
+    scopes.dbadmin(&db, None)?;
 
     log::info!("{{\"fn\": \"get_role\", \"db\": \"{}\", \"role\": \"{}\"}}", &db, &name);
     let payload = Find {
@@ -114,5 +115,5 @@ pub async fn get_role(
 
     log::debug!("Searching for roles with {:?}", payload);
 
-    state.db.find(&db, &"system.roles", payload, queries).await
+    state.db.find(&db, &"system.roles", payload, queries, framing, &QueriesConcern::default(), &scopes).await
 }
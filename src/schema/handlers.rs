@@ -0,0 +1,23 @@
+use axum::{
+    extract::{Path, Query},
+    Extension, Json,
+};
+use serde_json::Value;
+
+use crate::error::Error as RestError;
+use crate::schema::structs::QueriesSchema;
+use crate::scopes::AuthorizeScope;
+use crate::State;
+
+pub async fn schema(
+    Extension(state): Extension<State>,
+    Extension(scopes): Extension<AuthorizeScope>,
+    Path((db, coll)): Path<(String, String)>,
+    queries: Query<QueriesSchema>,
+) -> Result<Json<Value>, RestError> {
+    // Validate that the client has access to this database
+    scopes.read(&db, Some(&coll))?;
+
+    log::info!("{{\"fn\": \"schema\", \"db\": \"{}\", \"coll\": \"{}\"}}", &db, &coll);
+    Ok(Json(state.db.schema(&db, &coll, &queries, &scopes).await?))
+}
@@ -0,0 +1,201 @@
+use bson::{Bson, Document};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+
+// Examples kept per field
+const MAX_EXAMPLES: usize = 3;
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct QueriesSchema {
+    pub sample_size: Option<i64>,
+    pub json_schema: Option<bool>,
+}
+
+// Field paths, types and frequencies observed across sampled documents
+#[derive(Debug, Default)]
+pub struct Schema {
+    sampled: u64,
+    root: Field,
+}
+
+#[derive(Debug, Default)]
+struct Field {
+    count: u64,
+    types: BTreeMap<&'static str, u64>,
+    examples: Vec<Value>,
+    // Fields of embedded documents, in order of first appearance
+    fields: Vec<(String, Field)>,
+    // Elements of arrays
+    items: Option<Box<Field>>,
+}
+
+impl Schema {
+    pub fn add(&mut self, doc: &Document) {
+        self.sampled += 1;
+        self.root.observe_fields(doc);
+    }
+
+    pub fn report(&self, json_schema: bool) -> Value {
+        let mut fields = Vec::new();
+        self.root.describe("", self.sampled, &mut fields);
+
+        let mut report = json!({
+            "sampled": self.sampled,
+            "fields": fields,
+        });
+
+        // A draft validator, ready for collMod or createCollection
+        if json_schema {
+            report["validator"] = json!({ "$jsonSchema": self.root.object_schema(self.sampled) });
+        }
+
+        report
+    }
+}
+
+impl Field {
+    fn observe(&mut self, value: &Bson) {
+        self.count += 1;
+        *self.types.entry(type_name(value)).or_default() += 1;
+
+        match value {
+            Bson::Document(doc) => self.observe_fields(doc),
+            Bson::Array(items) => {
+                let field = self.items.get_or_insert_with(Default::default);
+                for item in items {
+                    field.observe(item);
+                }
+            }
+            _ => {
+                let example = value.clone().into_relaxed_extjson();
+                if self.examples.len() < MAX_EXAMPLES && !self.examples.contains(&example) {
+                    self.examples.push(example);
+                }
+            }
+        }
+    }
+
+    fn observe_fields(&mut self, doc: &Document) {
+        for (key, value) in doc {
+            match self.fields.iter_mut().find(|(k, _)| k == key) {
+                Some((_, field)) => field.observe(value),
+                None => {
+                    let mut field = Field::default();
+                    field.observe(value);
+                    self.fields.push((key.clone(), field));
+                }
+            }
+        }
+    }
+
+    // Number of times this field held an embedded document
+    fn objects(&self) -> u64 {
+        self.types.get("object").copied().unwrap_or_default()
+    }
+
+    // Flattens the embedded fields into dot notation paths, with array elements as [],
+    // where frequency is relative to how often the parent was a document
+    fn describe(&self, path: &str, parents: u64, out: &mut Vec<Value>) {
+        for (key, field) in &self.fields {
+            let path = if path.is_empty() {
+                key.to_string()
+            } else {
+                format!("{}.{}", path, key)
+            };
+            field.entry(&path, parents, out);
+        }
+    }
+
+    fn entry(&self, path: &str, parents: u64, out: &mut Vec<Value>) {
+        out.push(json!({
+            "path": path,
+            "count": self.count,
+            "frequency": self.count as f64 / parents.max(1) as f64,
+            "types": self.types,
+            "examples": self.examples,
+        }));
+
+        self.describe(path, self.objects(), out);
+        if let Some(ref items) = self.items {
+            items.entry(&format!("{}.[]", path), items.count, out);
+        }
+    }
+
+    fn schema(&self) -> Value {
+        let mut schema = Map::new();
+
+        let types: Vec<&str> = self.types.keys().copied().collect();
+        schema.insert(
+            "bsonType".to_string(),
+            match types.as_slice() {
+                [single] => json!(single),
+                _ => json!(types),
+            },
+        );
+
+        // Keep the full list of types, in case this wasn't always a document
+        if self.objects() > 0 {
+            let mut object = self.object_schema(self.objects());
+            object.remove("bsonType");
+            schema.extend(object);
+        }
+
+        if let Some(ref items) = self.items {
+            schema.insert("items".to_string(), items.schema());
+        }
+
+        Value::Object(schema)
+    }
+
+    // Fields present in every document are marked as required
+    fn object_schema(&self, objects: u64) -> Map<String, Value> {
+        let required: Vec<&str> = self
+            .fields
+            .iter()
+            .filter(|(_, field)| objects > 0 && field.count >= objects)
+            .map(|(key, _)| key.as_str())
+            .collect();
+
+        let properties: Map<String, Value> = self
+            .fields
+            .iter()
+            .map(|(key, field)| (key.clone(), field.schema()))
+            .collect();
+
+        let mut schema = Map::new();
+        schema.insert("bsonType".to_string(), json!("object"));
+        if !required.is_empty() {
+            schema.insert("required".to_string(), json!(required));
+        }
+        schema.insert("properties".to_string(), Value::Object(properties));
+        schema
+    }
+}
+
+// The same aliases $type and $jsonSchema use
+fn type_name(value: &Bson) -> &'static str {
+    match value {
+        Bson::Double(_) => "double",
+        Bson::String(_) => "string",
+        Bson::Document(_) => "object",
+        Bson::Array(_) => "array",
+        Bson::Binary(_) => "binData",
+        Bson::Undefined => "undefined",
+        Bson::ObjectId(_) => "objectId",
+        Bson::Boolean(_) => "bool",
+        Bson::DateTime(_) => "date",
+        Bson::Null => "null",
+        Bson::RegularExpression(_) => "regex",
+        Bson::DbPointer(_) => "dbPointer",
+        Bson::JavaScriptCode(_) => "javascript",
+        Bson::Symbol(_) => "symbol",
+        Bson::JavaScriptCodeWithScope(_) => "javascriptWithScope",
+        Bson::Int32(_) => "int",
+        Bson::Timestamp(_) => "timestamp",
+        Bson::Int64(_) => "long",
+        Bson::Decimal128(_) => "decimal",
+        Bson::MinKey => "minKey",
+        Bson::MaxKey => "maxKey",
+    }
+}
@@ -1,20 +1,31 @@
 use std::collections::HashMap;
+use bson::{Bson, Document};
 use chrono::offset::Utc;
 use chrono::{DateTime, TimeZone};
 use std::fmt;
 
 use crate::error::Error as RestError;
 use crate::auth::Claims;
+use crate::redaction::{Redaction, Redactions};
 
 #[derive(Clone)]
 pub struct AuthorizeScope {
     noauth: bool,
+    iss: Option<String>,
     sub: String,
-    exp: DateTime<Utc>,
+    exp: Option<DateTime<Utc>>,
     jti: String,
+    // Keyed by database, or by db.coll for roles on a single collection
     roles: HashMap<String, Vec<String>>,
+    redactions: Redactions,
 }
 
+// Who a transaction or cursor belongs to, as a sub is only unique within its issuer
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Owner {
+    pub iss: Option<String>,
+    pub sub: String,
+}
 
 // Roles in admin db
 const ADMIN_READ_ROLES: &'static [&'static str] = &[
@@ -40,7 +51,20 @@ impl fmt::Display for AuthorizeScope {
         let roles: Vec<String> = self.roles.iter().map(|(k,v)| {
             format!("{}:{}", k, v.join(":"))
         }).collect(); 
-        write!(f, "sub={}, jti={}, exp={}, roles={}", self.sub, self.jti, self.exp, roles.join(","))
+        let exp = match self.exp {
+            Some(exp) => exp.to_string(),
+            None => "never".to_string(),
+        };
+        write!(f, "sub={}, jti={}, exp={}, roles={}", self.sub, self.jti, exp, roles.join(","))
+    }
+}
+
+impl fmt::Display for Owner {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.iss {
+            Some(ref iss) => write!(f, "sub={}, iss={}", self.sub, iss),
+            None => write!(f, "sub={}", self.sub),
+        }
     }
 }
 
@@ -49,24 +73,69 @@ impl AuthorizeScope {
     pub fn default() -> Self {
         AuthorizeScope {
             noauth: true,
+            iss: None,
             sub: "noauth".to_string(),
-            exp: Utc::now(),
+            exp: Some(Utc::now()),
             jti: String::new(),
             roles: HashMap::new(),
+            redactions: Redactions::default(),
         }
     }
 
+    pub fn with_redactions(mut self, redactions: Redactions) -> Self {
+        self.redactions = redactions;
+        self
+    }
+
+    // Fields this client may not see in a collection
+    pub fn redaction(&self, db: &str, coll: &str) -> Redaction {
+        self.redactions.collection(db, coll)
+    }
+
+    pub fn redactions(&self) -> Redactions {
+        self.redactions.clone()
+    }
+
+    pub fn sub(&self) -> String {
+        self.sub.clone()
+    }
+
+    pub fn owner(&self) -> Owner {
+        Owner {
+            iss: self.iss.clone(),
+            sub: self.sub.clone(),
+        }
+    }
+
+    // Time left before the token expires, if auth is enabled and the token has an exp
+    pub fn expires_in(&self) -> Option<std::time::Duration> {
+        if self.noauth {
+            return None;
+        }
+        self.exp
+            .map(|exp| (exp - Utc::now()).to_std().unwrap_or_default())
+    }
+
     pub fn roles(&self) -> HashMap<String, Vec<String>> {
         self.roles.clone()
     }
 
     pub fn authorized_dbs(&self) -> Vec<String> {
-        // Return all keys, skipping cluster key
-        self.roles
-            .clone()
-            .into_keys()
+        // Return all keys, skipping cluster key, with collection roles counting towards their database
+        let mut dbs: Vec<String> = self
+            .roles
+            .keys()
             .filter(|k| k != &"cluster")
-            .collect()
+            .map(|k| k.split('.').next().unwrap_or_default().to_string())
+            .collect();
+        dbs.sort();
+        dbs.dedup();
+        dbs
+    }
+
+    // Roles granted on a single collection, database names can't contain a period so the keys can't clash
+    fn collection_roles(&self, db: &str, coll: Option<&str>) -> Option<&Vec<String>> {
+        self.roles.get(&format!("{}.{}", db, coll?))
     }
 
     pub fn new(
@@ -93,7 +162,8 @@ impl AuthorizeScope {
                 None => continue,
             };
 
-            let period_split: Vec<&str> = colon_split[0].split('.').collect();
+            // The value is either a database, a db.coll or a replicaset name
+            let period_split: Vec<&str> = colon_split[0].splitn(3, '.').collect();
 
             // This should always be mongodb
             match period_split.get(0) {
@@ -174,14 +244,16 @@ impl AuthorizeScope {
         log::debug!("\"sub={}, Scope map: {:?}\"", claims.sub, map);
         Ok(AuthorizeScope {
             noauth: false,
+            iss: claims.iss,
             sub: claims.sub,
             jti: claims.jti,
-            exp: Utc.timestamp(claims.exp, 0),
+            exp: claims.exp.map(|exp| Utc.timestamp(exp, 0)),
             roles: map,
+            redactions: Redactions::default(),
         })
     }
 
-    pub fn monitor(&self, db: &str) -> Result<(), RestError> {
+    pub fn monitor(&self, db: &str, coll: Option<&str>) -> Result<(), RestError> {
         // return early if noauth is true
         if self.noauth {
             log::debug!("\"sub={}, No cluster auth, exiting monitor fn\"", self.sub);
@@ -212,16 +284,32 @@ impl AuthorizeScope {
             }
         };
 
+        // Check if client has monitor rights to requested collection
+        if let Some(roles) = self.collection_roles(db, coll) {
+            for role in roles {
+                if DB_MONITOR_ROLES.contains(&role.as_str()) {
+                    log::debug!(
+                        "\"sub={}, db={}, coll={}, role=monitor, action=allow\"",
+                        self.sub,
+                        db,
+                        coll.unwrap_or_default()
+                    );
+                    return Ok(());
+                }
+            }
+        };
+
         // If we got here, there were no matched roles
         log::warn!(
-            "\"sub={}, db={}, role=monitor, action=reject\"",
+            "\"sub={}, db={}, coll={}, role=monitor, action=reject\"",
             self.sub,
-            db
+            db,
+            coll.unwrap_or_default()
         );
         Err(RestError::UnauthorizedClient)
     }
 
-    pub fn write(&self, db: &str) -> Result<(), RestError> {
+    pub fn write(&self, db: &str, coll: Option<&str>) -> Result<(), RestError> {
         // return early if noauth is true
         if self.noauth {
             log::debug!("\"sub={}, No cluster auth, exiting write fn\"", self.sub);
@@ -248,12 +336,32 @@ impl AuthorizeScope {
             }
         };
 
+        // Check if client has write rights to requested collection
+        if let Some(roles) = self.collection_roles(db, coll) {
+            for role in roles {
+                if DB_WRITE_ROLES.contains(&role.as_str()) {
+                    log::debug!(
+                        "\"sub={}, db={}, coll={}, role=write, action=allow\"",
+                        self.sub,
+                        db,
+                        coll.unwrap_or_default()
+                    );
+                    return Ok(());
+                }
+            }
+        };
+
         // If we got here, there were no matched roles
-        log::warn!("\"sub={}, db={}, role=write, action=reject\"", self.sub, db);
+        log::warn!(
+            "\"sub={}, db={}, coll={}, role=write, action=reject\"",
+            self.sub,
+            db,
+            coll.unwrap_or_default()
+        );
         Err(RestError::UnauthorizedClient)
     }
 
-    pub fn dbadmin(&self, db: &str) -> Result<(), RestError> {
+    pub fn dbadmin(&self, db: &str, coll: Option<&str>) -> Result<(), RestError> {
         // return early if noauth is true
         if self.noauth {
             log::debug!("\"sub={}, No cluster auth, exiting dbadmin fn\"", self.sub);
@@ -284,16 +392,32 @@ impl AuthorizeScope {
             }
         };
 
+        // Check if client has dbadmin rights to requested collection
+        if let Some(roles) = self.collection_roles(db, coll) {
+            for role in roles {
+                if DB_DBADMIN_ROLES.contains(&role.as_str()) {
+                    log::debug!(
+                        "\"sub={}, db={}, coll={}, role=dbadmin, action=allow\"",
+                        self.sub,
+                        db,
+                        coll.unwrap_or_default()
+                    );
+                    return Ok(());
+                }
+            }
+        };
+
         // If we got here, there were no matched roles
         log::warn!(
-            "\"sub={}, db={}, role=dbadmin, action=reject\"",
+            "\"sub={}, db={}, coll={}, role=dbadmin, action=reject\"",
             self.sub,
-            db
+            db,
+            coll.unwrap_or_default()
         );
         Err(RestError::UnauthorizedClient)
     }
 
-    pub fn read(&self, db: &str) -> Result<(), RestError> {
+    pub fn read(&self, db: &str, coll: Option<&str>) -> Result<(), RestError> {
         // return early if noauth is true
         if self.noauth {
             log::debug!("\"sub={}, No cluster auth, exiting read fn\"", self.sub);
@@ -320,8 +444,199 @@ impl AuthorizeScope {
             }
         };
 
+        // Check if client has read rights to requested collection
+        if let Some(roles) = self.collection_roles(db, coll) {
+            for role in roles {
+                if DB_READ_ROLES.contains(&role.as_str()) {
+                    log::debug!(
+                        "\"sub={}, db={}, coll={}, role=read, action=allow\"",
+                        self.sub,
+                        db,
+                        coll.unwrap_or_default()
+                    );
+                    return Ok(());
+                }
+            }
+        };
+
         // If we got here, there were no matched roles
-        log::warn!("\"sub={}, db={}, role=read, action=reject\"", self.sub, db);
+        log::warn!(
+            "\"sub={}, db={}, coll={}, role=read, action=reject\"",
+            self.sub,
+            db,
+            coll.unwrap_or_default()
+        );
         Err(RestError::UnauthorizedClient)
     }
+
+    // Databases the client can read, or None when it can read every database
+    pub fn read_dbs(&self) -> Option<Vec<String>> {
+        if self.noauth {
+            return None;
+        }
+
+        if let Some(roles) = self.roles.get("admin") {
+            if roles.iter().any(|role| ADMIN_READ_ROLES.contains(&role.as_str())) {
+                return None;
+            }
+        };
+
+        Some(
+            self.roles
+                .iter()
+                .filter(|(db, _)| db != &"cluster" && !db.contains('.'))
+                .filter(|(_, roles)| roles.iter().any(|role| DB_READ_ROLES.contains(&role.as_str())))
+                .map(|(db, _)| db.clone())
+                .collect(),
+        )
+    }
+
+    // Aggregations need write access to their collection, and access to every other collection they touch
+    pub fn aggregate(&self, db: &str, coll: &str, pipeline: &[Document]) -> Result<(), RestError> {
+        self.write(db, Some(coll))?;
+        self.pipeline(db, pipeline)
+    }
+
+    fn pipeline(&self, db: &str, pipeline: &[Document]) -> Result<(), RestError> {
+        for stage in pipeline {
+            for (name, spec) in stage {
+                match (name.as_str(), spec) {
+                    ("$lookup" | "$graphLookup", Bson::Document(spec)) => {
+                        if let Ok(from) = spec.get_str("from") {
+                            self.read(db, Some(from))?;
+                        };
+                        self.nested(db, spec.get("pipeline"))?;
+                    }
+                    ("$unionWith", Bson::String(from)) => self.read(db, Some(from))?,
+                    ("$unionWith", Bson::Document(spec)) => {
+                        if let Ok(from) = spec.get_str("coll") {
+                            self.read(db, Some(from))?;
+                        };
+                        self.nested(db, spec.get("pipeline"))?;
+                    }
+                    ("$facet", Bson::Document(spec)) => {
+                        for (_, pipeline) in spec {
+                            self.nested(db, Some(pipeline))?;
+                        }
+                    }
+                    ("$out", Bson::String(into)) => self.write(db, Some(into))?,
+                    ("$out", Bson::Document(into)) => self.target(db, into)?,
+                    ("$merge", Bson::String(into)) => self.write(db, Some(into))?,
+                    ("$merge", Bson::Document(spec)) => match spec.get("into") {
+                        Some(Bson::String(into)) => self.write(db, Some(into))?,
+                        Some(Bson::Document(into)) => self.target(db, into)?,
+                        _ => (),
+                    },
+                    _ => (),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn nested(&self, db: &str, pipeline: Option<&Bson>) -> Result<(), RestError> {
+        if let Some(Bson::Array(stages)) = pipeline {
+            let stages: Vec<Document> = stages
+                .iter()
+                .filter_map(|stage| stage.as_document().cloned())
+                .collect();
+            self.pipeline(db, &stages)?;
+        };
+        Ok(())
+    }
+
+    // $out and $merge can write to another database with {db, coll}
+    fn target(&self, db: &str, into: &Document) -> Result<(), RestError> {
+        let db = into.get_str("db").unwrap_or(db);
+        match into.get_str("coll") {
+            Ok(coll) => self.write(db, Some(coll)),
+            Err(_) => self.write(db, None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bson::doc;
+
+    fn scopes(scp: &[&str]) -> AuthorizeScope {
+        let mut scp: Vec<String> = scp.iter().map(|scope| scope.to_string()).collect();
+        scp.push("mongodb.cluster.rs0:allow".to_string());
+        let claims = Claims {
+            sub: "client".to_string(),
+            exp: None,
+            iss: None,
+            jti: String::new(),
+            scp,
+        };
+        AuthorizeScope::new(Some(vec!["rs0".to_string()]), claims).unwrap()
+    }
+
+    #[test]
+    fn collection_role_grants_only_its_collection() {
+        let scopes = scopes(&["mongodb.role.sales.orders:read"]);
+        assert!(scopes.read("sales", Some("orders")).is_ok());
+        assert!(scopes.read("sales", Some("customers")).is_err());
+        assert!(scopes.read("sales", None).is_err());
+        assert!(scopes.write("sales", Some("orders")).is_err());
+        assert_eq!(scopes.authorized_dbs(), vec!["sales"]);
+        assert_eq!(scopes.read_dbs(), Some(Vec::new()));
+    }
+
+    #[test]
+    fn database_role_covers_every_collection() {
+        let scopes = scopes(&["mongodb.role.sales:readwrite"]);
+        assert!(scopes.read("sales", Some("orders")).is_ok());
+        assert!(scopes.write("sales", Some("customers")).is_ok());
+        assert!(scopes.read("sales", None).is_ok());
+        assert!(scopes.read("other", Some("orders")).is_err());
+    }
+
+    #[test]
+    fn dotted_collection_names_keep_their_dots() {
+        let scopes = scopes(&["mongodb.role.sales.orders.archive:read"]);
+        assert!(scopes.read("sales", Some("orders.archive")).is_ok());
+        assert!(scopes.read("sales", Some("orders")).is_err());
+    }
+
+    #[test]
+    fn cluster_scope_is_required() {
+        let claims = Claims {
+            sub: "client".to_string(),
+            exp: None,
+            iss: None,
+            jti: String::new(),
+            scp: vec!["mongodb.role.sales:read".to_string()],
+        };
+        assert!(AuthorizeScope::new(Some(vec!["rs0".to_string()]), claims).is_err());
+    }
+
+    #[test]
+    fn aggregate_checks_collections_it_touches() {
+        let scopes = scopes(&[
+            "mongodb.role.sales.orders:readwrite",
+            "mongodb.role.sales.products:read",
+        ]);
+        let lookup = |from: &str| {
+            vec![doc! {"$lookup": {"from": from, "localField": "sku", "foreignField": "sku", "as": "item"}}]
+        };
+        assert!(scopes.aggregate("sales", "orders", &lookup("products")).is_ok());
+        assert!(scopes.aggregate("sales", "orders", &lookup("customers")).is_err());
+
+        // Nested pipelines, unions and facets are checked too
+        let nested = vec![doc! {"$facet": {"all": [{"$unionWith": {"coll": "customers", "pipeline": []}}]}}];
+        assert!(scopes.aggregate("sales", "orders", &nested).is_err());
+        assert!(scopes.aggregate("sales", "orders", &[doc! {"$unionWith": "products"}]).is_ok());
+
+        // Writing out needs write access to the target
+        assert!(scopes.aggregate("sales", "orders", &[doc! {"$out": "products"}]).is_err());
+        assert!(scopes
+            .aggregate("sales", "orders", &[doc! {"$merge": {"into": {"db": "other", "coll": "orders"}}}])
+            .is_err());
+        assert!(scopes.aggregate("sales", "orders", &[doc! {"$out": "orders"}]).is_ok());
+
+        // The collection being aggregated needs write access, as $out and $merge can write to it
+        assert!(scopes.aggregate("sales", "products", &[]).is_err());
+    }
 }
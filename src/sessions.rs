@@ -0,0 +1,236 @@
+use axum::{async_trait, extract::FromRequestParts, http::request::Parts};
+use bson::oid::ObjectId;
+use chrono::Utc;
+use core::time::Duration;
+use mongodb::{options::TransactionOptions, Client, ClientSession};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+
+use crate::error::Error as RestError;
+use crate::scopes::{AuthorizeScope, Owner};
+
+type Result<T> = std::result::Result<T, RestError>;
+
+const TRANSACTION_HEADER: &str = "x-transaction-id";
+
+#[derive(Debug)]
+pub struct Session {
+    owner: Owner,
+    last_used: i64,
+    pub session: ClientSession,
+}
+
+#[derive(Clone, Debug)]
+pub struct Sessions {
+    sessions: Arc<Mutex<HashMap<String, Arc<AsyncMutex<Session>>>>>,
+    idle_timeout: i64,
+}
+
+#[derive(Clone, Debug)]
+pub struct TransactionId {
+    pub id: String,
+    pub owner: Owner,
+}
+
+// Transaction requested with the X-Transaction-Id header, tied to the token's iss and sub
+#[derive(Clone, Debug, Default)]
+pub struct Transaction(pub Option<TransactionId>);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Transaction
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &S,
+    ) -> std::result::Result<Self, Self::Rejection> {
+        let id = match parts
+            .headers
+            .get(TRANSACTION_HEADER)
+            .and_then(|header| header.to_str().ok())
+        {
+            Some(id) => id.to_string(),
+            None => return Ok(Transaction(None)),
+        };
+
+        let owner = match parts.extensions.get::<AuthorizeScope>() {
+            Some(scopes) => scopes.owner(),
+            None => Owner::default(),
+        };
+
+        Ok(Transaction(Some(TransactionId { id, owner })))
+    }
+}
+
+// Run a driver call in the transaction's session when there is one. The driver has a
+// separate _with_session method for every call, so both calls are passed in
+macro_rules! with_session {
+    ($session:expr, |$client:ident| $with:expr, $without:expr) => {
+        match $session {
+            Some(session) => {
+                let $client = &mut session.session;
+                $with.await
+            }
+            None => $without.await,
+        }
+    };
+}
+pub(crate) use with_session;
+
+impl Sessions {
+    pub fn new(idle_timeout: u64) -> Self {
+        Sessions {
+            sessions: Arc::new(Mutex::new(HashMap::new())),
+            idle_timeout: idle_timeout as i64,
+        }
+    }
+
+    pub async fn start(
+        &self,
+        client: &Client,
+        owner: &Owner,
+        options: Option<TransactionOptions>,
+    ) -> Result<String> {
+        let mut session = client.start_session(None).await?;
+        session.start_transaction(options).await?;
+
+        let id = ObjectId::new().to_hex();
+        let session = Session {
+            owner: owner.clone(),
+            last_used: Utc::now().timestamp(),
+            session,
+        };
+
+        log::debug!("\"{}, Started transaction {}\"", owner, id);
+        metrics::increment_counter!("mongodb_rest_transactions_started_total");
+
+        self.sessions
+            .lock()
+            .unwrap()
+            .insert(id.clone(), Arc::new(AsyncMutex::new(session)));
+
+        Ok(id)
+    }
+
+    // Get exclusive use of the session for the requested transaction, if any
+    pub async fn lock(
+        &self,
+        transaction: &Transaction,
+    ) -> Result<Option<OwnedMutexGuard<Session>>> {
+        let transaction = match transaction.0 {
+            Some(ref t) => t,
+            None => return Ok(None),
+        };
+
+        let session = self.get(&transaction.id)?;
+        let mut guard = session.clone().lock_owned().await;
+
+        // The transaction may have been committed, aborted or expired while we waited
+        match self.get(&transaction.id) {
+            Ok(current) if Arc::ptr_eq(&current, &session) => (),
+            _ => return Err(RestError::TransactionNotFound),
+        };
+
+        if guard.owner != transaction.owner {
+            log::warn!(
+                "\"{}, transaction={}, action=reject\"",
+                transaction.owner,
+                transaction.id
+            );
+            return Err(RestError::UnauthorizedClient);
+        }
+
+        guard.last_used = Utc::now().timestamp();
+        Ok(Some(guard))
+    }
+
+    pub async fn commit(&self, id: &str, owner: &Owner) -> Result<()> {
+        let mut session = self.take(id, owner).await?;
+        log::debug!("\"{}, Committing transaction {}\"", owner, id);
+        session.session.commit_transaction().await?;
+        metrics::increment_counter!("mongodb_rest_transactions_committed_total");
+        Ok(())
+    }
+
+    pub async fn abort(&self, id: &str, owner: &Owner) -> Result<()> {
+        let mut session = self.take(id, owner).await?;
+        log::debug!("\"{}, Aborting transaction {}\"", owner, id);
+        session.session.abort_transaction().await?;
+        metrics::increment_counter!("mongodb_rest_transactions_aborted_total");
+        Ok(())
+    }
+
+    // Kick off background job to abort transactions that have sat idle for too long
+    pub fn reap(&self) {
+        let me = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(5));
+            loop {
+                interval.tick().await;
+                me.expire().await;
+            }
+        });
+    }
+
+    async fn expire(&self) {
+        let now = Utc::now().timestamp();
+        let mut expired = Vec::new();
+
+        {
+            let mut sessions = self.sessions.lock().unwrap();
+            sessions.retain(|id, session| {
+                // Sessions currently in use are never idle
+                match session.clone().try_lock_owned() {
+                    Ok(guard) if now - guard.last_used >= self.idle_timeout => {
+                        log::info!(
+                            "\"{}, Transaction {} has expired, aborting\"",
+                            guard.owner,
+                            id
+                        );
+                        expired.push(guard);
+                        false
+                    }
+                    _ => true,
+                }
+            });
+        }
+
+        for mut guard in expired {
+            metrics::increment_counter!("mongodb_rest_transactions_expired_total");
+            if let Err(e) = guard.session.abort_transaction().await {
+                log::error!("Error aborting expired transaction: {}", e);
+            }
+        }
+    }
+
+    fn get(&self, id: &str) -> Result<Arc<AsyncMutex<Session>>> {
+        match self.sessions.lock().unwrap().get(id) {
+            Some(session) => Ok(session.clone()),
+            None => {
+                log::debug!("Transaction {} not found", id);
+                Err(RestError::TransactionNotFound)
+            }
+        }
+    }
+
+    async fn take(&self, id: &str, owner: &Owner) -> Result<OwnedMutexGuard<Session>> {
+        let transaction = Transaction(Some(TransactionId {
+            id: id.to_string(),
+            owner: owner.clone(),
+        }));
+
+        // Wait for any in flight operation, then remove the transaction so it can't be used again
+        let guard = match self.lock(&transaction).await? {
+            Some(guard) => guard,
+            None => return Err(RestError::TransactionNotFound),
+        };
+        self.sessions.lock().unwrap().remove(id);
+
+        Ok(guard)
+    }
+}
@@ -30,7 +30,13 @@ impl State {
             None => ClientOptions::parse(&args.uri).await?,
         };
 
-        let db = DB::init(client, args.readonly).await?;
+        let db = DB::init(
+            client,
+            args.readonly,
+            args.transaction_timeout,
+            args.cursor_timeout,
+        )
+        .await?;
 
         Ok(State { db })
     }
@@ -0,0 +1,41 @@
+use axum::{extract::Path, Extension, Json};
+use mongodb::options::TransactionOptions;
+use serde_json::{json, Value};
+
+use crate::error::Error as RestError;
+use crate::scopes::AuthorizeScope;
+use crate::State;
+
+pub async fn start_transaction(
+    Extension(state): Extension<State>,
+    Extension(scopes): Extension<AuthorizeScope>,
+    payload: Option<Json<TransactionOptions>>,
+) -> Result<Json<Value>, RestError> {
+    log::info!("{{\"fn\": \"start_transaction\", \"sub\": \"{}\"}}", scopes.sub());
+    let options = payload.map(|Json(options)| options);
+    Ok(Json(json!(
+        state.db.start_transaction(&scopes.owner(), options).await?
+    )))
+}
+
+pub async fn commit_transaction(
+    Extension(state): Extension<State>,
+    Extension(scopes): Extension<AuthorizeScope>,
+    Path(id): Path<String>,
+) -> Result<Json<Value>, RestError> {
+    log::info!("{{\"fn\": \"commit_transaction\", \"transaction\": \"{}\"}}", &id);
+    Ok(Json(json!(
+        state.db.commit_transaction(&id, &scopes.owner()).await?
+    )))
+}
+
+pub async fn abort_transaction(
+    Extension(state): Extension<State>,
+    Extension(scopes): Extension<AuthorizeScope>,
+    Path(id): Path<String>,
+) -> Result<Json<Value>, RestError> {
+    log::info!("{{\"fn\": \"abort_transaction\", \"transaction\": \"{}\"}}", &id);
+    Ok(Json(json!(
+        state.db.abort_transaction(&id, &scopes.owner()).await?
+    )))
+}
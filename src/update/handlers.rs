@@ -1,35 +1,100 @@
-use axum::{extract::Path, Extension, Json};
+use axum::{
+    extract::{Path, Query},
+    Extension, Json,
+};
 use serde_json::{json, Value};
 
+use crate::body::Payload;
 use crate::error::Error as RestError;
+use crate::queries::{QueriesConcern, QueriesFormat};
 use crate::scopes::AuthorizeScope;
-use crate::update::structs::Update;
+use crate::sessions::Transaction;
+use crate::update::structs::{FindOneAndReplace, FindOneAndUpdate, Replace, Update};
 use crate::State;
 
 pub async fn update_one(
     Extension(state): Extension<State>,
     Extension(scopes): Extension<AuthorizeScope>,
     Path((db, coll)): Path<(String, String)>,
-    Json(payload): Json<Update>,
+    concern: Query<QueriesConcern>,
+    transaction: Transaction,
+    Payload(payload): Payload<Update>,
 ) -> Result<Json<Value>, RestError> {
     // Validate that the client has access to this database
-    scopes.write(&db)?;
+    scopes.write(&db, Some(&coll))?;
 
     log::info!("{{\"fn\": \"update_one\", \"db\": \"{}\", \"coll\": \"{}\"}}", &db, &coll);
-    Ok(Json(json!(state.db.update_one(&db, &coll, payload).await?)))
+    Ok(Json(json!(
+        state.db.update_one(&db, &coll, payload, &concern, transaction, &scopes).await?
+    )))
 }
 
 pub async fn update_many(
     Extension(state): Extension<State>,
     Extension(scopes): Extension<AuthorizeScope>,
     Path((db, coll)): Path<(String, String)>,
-    Json(payload): Json<Update>,
+    concern: Query<QueriesConcern>,
+    transaction: Transaction,
+    Payload(payload): Payload<Update>,
 ) -> Result<Json<Value>, RestError> {
     // Validate that the client has access to this database
-    scopes.write(&db)?;
+    scopes.write(&db, Some(&coll))?;
 
     log::info!("{{\"fn\": \"update_many\", \"db\": \"{}\", \"coll\": \"{}\"}}", &db, &coll);
     Ok(Json(json!(
-        state.db.update_many(&db, &coll, payload).await?
+        state.db.update_many(&db, &coll, payload, &concern, transaction, &scopes).await?
+    )))
+}
+
+pub async fn replace_one(
+    Extension(state): Extension<State>,
+    Extension(scopes): Extension<AuthorizeScope>,
+    Path((db, coll)): Path<(String, String)>,
+    concern: Query<QueriesConcern>,
+    transaction: Transaction,
+    Payload(payload): Payload<Replace>,
+) -> Result<Json<Value>, RestError> {
+    // Validate that the client has access to this database
+    scopes.write(&db, Some(&coll))?;
+
+    log::info!("{{\"fn\": \"replace_one\", \"db\": \"{}\", \"coll\": \"{}\"}}", &db, &coll);
+    Ok(Json(json!(
+        state.db.replace_one(&db, &coll, payload, &concern, transaction, &scopes).await?
+    )))
+}
+
+pub async fn find_one_and_update(
+    Extension(state): Extension<State>,
+    Extension(scopes): Extension<AuthorizeScope>,
+    Path((db, coll)): Path<(String, String)>,
+    transaction: Transaction,
+    queries: Query<QueriesFormat>,
+    concern: Query<QueriesConcern>,
+    Payload(payload): Payload<FindOneAndUpdate>,
+) -> Result<Json<Value>, RestError> {
+    // Validate that the client has access to this database
+    scopes.write(&db, Some(&coll))?;
+
+    log::info!("{{\"fn\": \"find_one_and_update\", \"db\": \"{}\", \"coll\": \"{}\"}}", &db, &coll);
+    Ok(Json(json!(
+        state.db.find_one_and_update(&db, &coll, payload, &queries, &concern, transaction, &scopes).await?
+    )))
+}
+
+pub async fn find_one_and_replace(
+    Extension(state): Extension<State>,
+    Extension(scopes): Extension<AuthorizeScope>,
+    Path((db, coll)): Path<(String, String)>,
+    transaction: Transaction,
+    queries: Query<QueriesFormat>,
+    concern: Query<QueriesConcern>,
+    Payload(payload): Payload<FindOneAndReplace>,
+) -> Result<Json<Value>, RestError> {
+    // Validate that the client has access to this database
+    scopes.write(&db, Some(&coll))?;
+
+    log::info!("{{\"fn\": \"find_one_and_replace\", \"db\": \"{}\", \"coll\": \"{}\"}}", &db, &coll);
+    Ok(Json(json!(
+        state.db.find_one_and_replace(&db, &coll, payload, &queries, &concern, transaction, &scopes).await?
     )))
 }
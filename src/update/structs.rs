@@ -1,5 +1,8 @@
 use bson::Document;
-use mongodb::options::{UpdateModifications, UpdateOptions};
+use mongodb::options::{
+    FindOneAndReplaceOptions, FindOneAndUpdateOptions, ReplaceOptions, UpdateModifications,
+    UpdateOptions,
+};
 use serde::Deserialize;
 
 #[derive(Deserialize, Debug, Clone)]
@@ -8,3 +11,25 @@ pub struct Update {
     pub update: UpdateModifications,
     pub options: Option<UpdateOptions>,
 }
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Replace {
+    pub filter: Document,
+    pub replacement: Document,
+    pub upsert: Option<bool>,
+    pub options: Option<ReplaceOptions>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct FindOneAndUpdate {
+    pub filter: Document,
+    pub update: UpdateModifications,
+    pub options: Option<FindOneAndUpdateOptions>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct FindOneAndReplace {
+    pub filter: Document,
+    pub replacement: Document,
+    pub options: Option<FindOneAndReplaceOptions>,
+}
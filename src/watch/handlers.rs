@@ -1,15 +1,14 @@
 use crate::error::Error as RestError;
-use crate::queries::QueriesFormat;
+use crate::framing::{Framing, LastEventId};
+use crate::queries::QueriesWatch;
 use crate::watch::structs::Watch;
 use crate::State;
 use axum::{
-    body::Bytes,
-    body::StreamBody,
     extract::{Path, Query},
+    response::IntoResponse,
     Extension, Json,
 };
 use bson::doc;
-use futures::Stream;
 
 use crate::scopes::AuthorizeScope;
 
@@ -17,29 +16,101 @@ pub async fn watch(
     Extension(state): Extension<State>,
     Extension(scopes): Extension<AuthorizeScope>,
     Path((db, coll)): Path<(String, String)>,
-    queries: Query<QueriesFormat>,
+    queries: Query<QueriesWatch>,
+    framing: Framing,
+    last_event_id: LastEventId,
     Json(payload): Json<Watch>,
-) -> Result<StreamBody<impl Stream<Item = Result<Bytes, RestError>>>, RestError> {
+) -> Result<impl IntoResponse, RestError> {
     // Validate that the client has access to this database
-    scopes.read(&db)?;
+    scopes.read(&db, Some(&coll))?;
 
     log::info!("{{\"fn\": \"watch\", \"db\":\"{}\", \"coll\":\"{}\"}}", &db, &coll);
-    state.db.watch(&db, &coll, payload, queries).await
+    state.db.watch(&db, &coll, payload, queries, framing, last_event_id, &scopes).await
 }
 
 pub async fn watch_latest(
     Extension(state): Extension<State>,
     Extension(scopes): Extension<AuthorizeScope>,
     Path((db, coll)): Path<(String, String)>,
-    queries: Query<QueriesFormat>,
-) -> Result<StreamBody<impl Stream<Item = Result<Bytes, RestError>>>, RestError> {
+    queries: Query<QueriesWatch>,
+    framing: Framing,
+    last_event_id: LastEventId,
+) -> Result<impl IntoResponse, RestError> {
     // Validate that the client has access to this database
-    scopes.read(&db)?;
+    scopes.read(&db, Some(&coll))?;
 
     log::info!("{{\"fn\": \"watch\", \"db\":\"{}\", \"coll\":\"{}\"}}", &db, &coll);
     let payload = Watch {
         pipeline: vec![doc! {"$match":{}}],
         options: None,
     };
-    state.db.watch(&db, &coll, payload, queries).await
+    state.db.watch(&db, &coll, payload, queries, framing, last_event_id, &scopes).await
+}
+
+pub async fn watch_database(
+    Extension(state): Extension<State>,
+    Extension(scopes): Extension<AuthorizeScope>,
+    Path(db): Path<String>,
+    queries: Query<QueriesWatch>,
+    framing: Framing,
+    last_event_id: LastEventId,
+    Json(payload): Json<Watch>,
+) -> Result<impl IntoResponse, RestError> {
+    // Validate that the client has access to this database
+    scopes.read(&db, None)?;
+
+    log::info!("{{\"fn\": \"watch_database\", \"db\":\"{}\"}}", &db);
+    state.db.watch_database(&db, payload, queries, framing, last_event_id, &scopes).await
+}
+
+pub async fn watch_database_latest(
+    Extension(state): Extension<State>,
+    Extension(scopes): Extension<AuthorizeScope>,
+    Path(db): Path<String>,
+    queries: Query<QueriesWatch>,
+    framing: Framing,
+    last_event_id: LastEventId,
+) -> Result<impl IntoResponse, RestError> {
+    // Validate that the client has access to this database
+    scopes.read(&db, None)?;
+
+    log::info!("{{\"fn\": \"watch_database\", \"db\":\"{}\"}}", &db);
+    let payload = Watch {
+        pipeline: vec![doc! {"$match":{}}],
+        options: None,
+    };
+    state.db.watch_database(&db, payload, queries, framing, last_event_id, &scopes).await
+}
+
+pub async fn watch_cluster(
+    Extension(state): Extension<State>,
+    Extension(scopes): Extension<AuthorizeScope>,
+    queries: Query<QueriesWatch>,
+    framing: Framing,
+    last_event_id: LastEventId,
+    Json(payload): Json<Watch>,
+) -> Result<impl IntoResponse, RestError> {
+    log::info!("{{\"fn\": \"watch_cluster\"}}");
+
+    // Clients without an admin read role only see events from the databases they can read
+    let databases = scopes.read_dbs();
+    state.db.watch_cluster(payload, queries, framing, last_event_id, databases, &scopes).await
+}
+
+pub async fn watch_cluster_latest(
+    Extension(state): Extension<State>,
+    Extension(scopes): Extension<AuthorizeScope>,
+    queries: Query<QueriesWatch>,
+    framing: Framing,
+    last_event_id: LastEventId,
+) -> Result<impl IntoResponse, RestError> {
+    log::info!("{{\"fn\": \"watch_cluster\"}}");
+    let payload = Watch {
+        pipeline: vec![doc! {"$match":{}}],
+        options: None,
+    };
+
+    // Clients without an admin read role only see events from the databases they can read
+    let databases = scopes.read_dbs();
+    state.db.watch_cluster(payload, queries, framing, last_event_id, databases, &scopes).await
 }
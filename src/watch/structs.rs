@@ -1,9 +1,25 @@
 use bson::Document;
+use mongodb::change_stream::event::{ChangeStreamEvent, ResumeToken};
 use mongodb::options::ChangeStreamOptions;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct Watch {
     pub pipeline: Vec<Document>,
     pub options: Option<ChangeStreamOptions>,
 }
+
+// Each line of a change stream, so clients always know where to resume from
+#[derive(Serialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WatchEvent {
+    Change {
+        resume_token: ResumeToken,
+        event: Box<ChangeStreamEvent<Document>>,
+    },
+    // Always the last line of the stream
+    Error {
+        resume_token: Option<ResumeToken>,
+        error: String,
+    },
+}
@@ -0,0 +1,273 @@
+use axum::{
+    extract::ws::{close_code, CloseFrame, Message, WebSocket, WebSocketUpgrade},
+    extract::Query,
+    http::{header::AUTHORIZATION, HeaderMap},
+    response::Response,
+    Extension,
+};
+use bson::{to_bson, Document};
+use futures::StreamExt;
+use mongodb::change_stream::event::ChangeStreamEvent;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::auth::AuthJwks;
+use crate::error::Error as RestError;
+use crate::queries::{Formats, QueriesFormat};
+use crate::scopes::AuthorizeScope;
+use crate::watch::structs::Watch;
+use crate::ws::structs::{ClientMessage, ServerMessage};
+use crate::State;
+
+const MAX_SUBSCRIPTIONS: usize = 100;
+
+// How long a socket opened without an Authorization header has to send its auth message
+const AUTH_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub async fn ws(
+    Extension(state): Extension<State>,
+    Extension(auth): Extension<AuthJwks>,
+    headers: HeaderMap,
+    queries: Query<QueriesFormat>,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    log::info!("{{\"fn\": \"ws\"}}");
+
+    // Browsers can't set headers on websockets, so the token may also come in the first message
+    let header = headers
+        .get(AUTHORIZATION)
+        .and_then(|header| header.to_str().ok())
+        .map(|header| header.to_string());
+
+    upgrade.on_upgrade(move |socket| session(socket, state, auth, header, queries.0))
+}
+
+async fn session(
+    mut socket: WebSocket,
+    state: State,
+    mut auth: AuthJwks,
+    header: Option<String>,
+    queries: QueriesFormat,
+) {
+    let scopes = match authenticate(&mut socket, &mut auth, header).await {
+        Ok(scopes) => scopes,
+        Err(e) => {
+            log::debug!("Got error authenticating websocket: {}", e);
+            send(&mut socket, &error(None, &e)).await;
+            let frame = CloseFrame {
+                code: close_code::POLICY,
+                reason: "Unauthorized".into(),
+            };
+            socket.send(Message::Close(Some(frame))).await.ok();
+            return;
+        }
+    };
+
+    log::info!("\"type=login, state=accepted, {}\"", scopes);
+    send(&mut socket, &ServerMessage::Authenticated).await;
+
+    // The socket is closed once the token expires
+    let expiry = scopes.expires_in();
+    let expired = async move {
+        match expiry {
+            Some(expiry) => tokio::time::sleep(expiry).await,
+            None => std::future::pending().await,
+        }
+    };
+    tokio::pin!(expired);
+
+    let (tx, mut rx) = mpsc::channel::<ServerMessage>(100);
+    let mut subscriptions: HashMap<String, JoinHandle<()>> = HashMap::new();
+
+    loop {
+        tokio::select! {
+            message = socket.recv() => {
+                let text = match message {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => continue,
+                };
+
+                let reply = match serde_json::from_str::<ClientMessage>(&text) {
+                    Ok(message) => {
+                        handle(message, &state, &scopes, &queries, &tx, &mut subscriptions).await
+                    }
+                    Err(e) => error(None, &RestError::BadRequest(e.to_string())),
+                };
+
+                if !send(&mut socket, &reply).await {
+                    break;
+                }
+            }
+            Some(message) = rx.recv() => {
+                if !send(&mut socket, &message).await {
+                    break;
+                }
+            }
+            _ = &mut expired => {
+                log::debug!("\"sub={}, Websocket token has expired\"", scopes.sub());
+                send(&mut socket, &error(None, &RestError::UnauthorizedClient)).await;
+                break;
+            }
+        }
+    }
+
+    // Dropping the change streams kills their cursors on the server
+    for (_, subscription) in subscriptions {
+        subscription.abort();
+    }
+}
+
+async fn authenticate(
+    socket: &mut WebSocket,
+    auth: &mut AuthJwks,
+    header: Option<String>,
+) -> Result<AuthorizeScope, RestError> {
+    if header.is_some() || auth.noauth() {
+        return auth.authorize(header.as_deref()).await;
+    }
+
+    // The auth middleware doesn't cover /ws, so sockets can't sit here unauthenticated
+    let text = match tokio::time::timeout(AUTH_TIMEOUT, socket.recv()).await {
+        Ok(Some(Ok(Message::Text(text)))) => text,
+        Ok(_) => return Err(RestError::UnauthorizedClient),
+        Err(_) => {
+            log::debug!("\"Websocket wasn't authenticated within {:?}\"", AUTH_TIMEOUT);
+            return Err(RestError::UnauthorizedClient);
+        }
+    };
+
+    match serde_json::from_str::<ClientMessage>(&text) {
+        Ok(ClientMessage::Auth { token }) => {
+            auth.authorize(Some(&format!("Bearer {}", token))).await
+        }
+        _ => Err(RestError::UnauthorizedClient),
+    }
+}
+
+async fn handle(
+    message: ClientMessage,
+    state: &State,
+    scopes: &AuthorizeScope,
+    queries: &QueriesFormat,
+    tx: &mpsc::Sender<ServerMessage>,
+    subscriptions: &mut HashMap<String, JoinHandle<()>>,
+) -> ServerMessage {
+    match message {
+        ClientMessage::Auth { .. } => error(
+            None,
+            &RestError::BadRequest("Already authenticated".to_string()),
+        ),
+        ClientMessage::Subscribe {
+            id,
+            db,
+            coll,
+            pipeline,
+            options,
+        } => {
+            // Validate that the client has access to this database
+            if let Err(e) = scopes.read(&db, Some(&coll)) {
+                return error(Some(id), &e);
+            }
+
+            // Subscriptions that have ended on their own can be replaced
+            subscriptions.retain(|_, subscription| !subscription.is_finished());
+
+            if subscriptions.contains_key(&id) {
+                let e = RestError::BadRequest(format!("Subscription {} already exists", id));
+                return error(Some(id), &e);
+            }
+
+            if subscriptions.len() >= MAX_SUBSCRIPTIONS {
+                let e = RestError::BadRequest("Too many subscriptions".to_string());
+                return error(Some(id), &e);
+            }
+
+            log::info!("{{\"fn\": \"ws_subscribe\", \"db\": \"{}\", \"coll\": \"{}\"}}", &db, &coll);
+            let payload = Watch {
+                pipeline,
+                options: options.map(|options| *options),
+            };
+            let stream = match state.db.change_stream(&db, &coll, payload, scopes).await {
+                Ok(stream) => stream,
+                Err(e) => return error(Some(id), &e),
+            };
+
+            let format = queries.format.clone();
+            let tx = tx.clone();
+            let subscription = id.clone();
+            let task = tokio::spawn(async move {
+                let mut stream = stream;
+                while let Some(event) = stream.next().await {
+                    let message = match render(event, &format) {
+                        Ok(event) => ServerMessage::Change {
+                            id: subscription.clone(),
+                            event,
+                        },
+                        Err(e) => {
+                            log::error!("Error in change stream: {}", e);
+                            let _ = tx.send(error(Some(subscription.clone()), &e)).await;
+                            break;
+                        }
+                    };
+
+                    // The socket has gone away
+                    if tx.send(message).await.is_err() {
+                        break;
+                    }
+                }
+            });
+
+            subscriptions.insert(id.clone(), task);
+            ServerMessage::Subscribed { id }
+        }
+        ClientMessage::Unsubscribe { id } => match subscriptions.remove(&id) {
+            Some(subscription) => {
+                subscription.abort();
+                ServerMessage::Unsubscribed { id }
+            }
+            None => {
+                let e = RestError::BadRequest(format!("Subscription {} not found", id));
+                error(Some(id), &e)
+            }
+        },
+    }
+}
+
+fn render(
+    event: mongodb::error::Result<ChangeStreamEvent<Document>>,
+    format: &Option<Formats>,
+) -> Result<Value, RestError> {
+    let bson = to_bson(&event?)?;
+    match format {
+        Some(Formats::Ejson) => Ok(bson.into_canonical_extjson()),
+        _ => Ok(bson.into_relaxed_extjson()),
+    }
+}
+
+// Errors render as json already, so pull out just the message
+fn error(id: Option<String>, e: &RestError) -> ServerMessage {
+    let rendered = e.to_string();
+    let error = serde_json::from_str::<Value>(&rendered)
+        .ok()
+        .and_then(|value| value["error"].as_str().map(|e| e.to_string()))
+        .unwrap_or(rendered);
+
+    ServerMessage::Error { id, error }
+}
+
+// Returns false once the socket can no longer be written to
+async fn send(socket: &mut WebSocket, message: &ServerMessage) -> bool {
+    let text = match serde_json::to_string(message) {
+        Ok(text) => text,
+        Err(e) => {
+            log::error!("Error serializing websocket message: {}", e);
+            return true;
+        }
+    };
+
+    socket.send(Message::Text(text)).await.is_ok()
+}
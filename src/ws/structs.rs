@@ -0,0 +1,44 @@
+use bson::Document;
+use mongodb::options::ChangeStreamOptions;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ClientMessage {
+    Auth {
+        token: String,
+    },
+    Subscribe {
+        id: String,
+        db: String,
+        coll: String,
+        #[serde(default)]
+        pipeline: Vec<Document>,
+        options: Option<Box<ChangeStreamOptions>>,
+    },
+    Unsubscribe {
+        id: String,
+    },
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    Authenticated,
+    Subscribed {
+        id: String,
+    },
+    Unsubscribed {
+        id: String,
+    },
+    Change {
+        id: String,
+        event: Value,
+    },
+    Error {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        error: String,
+    },
+}